@@ -0,0 +1,167 @@
+use crate::Ray;
+use crate::Vec3;
+
+/// Axis-aligned bounding box, inclusive of its `min` and `max` corners.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// The box which contains nothing and is hit by no ray, any union with it
+    /// leaves the other box unchanged.
+    pub fn empty() -> Self {
+        Self::new(
+            Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        )
+    }
+
+    /// The smallest box containing all of the `points`.
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Self {
+        points.into_iter().fold(Self::empty(), |aabb, point| {
+            aabb.union(&Self::new(point, point))
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            Vec3::min(self.min, other.min),
+            Vec3::max(self.max, other.max),
+        )
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let extent = self.max - self.min;
+        2.0 * (extent.x() * extent.y() + extent.y() * extent.z() + extent.z() * extent.x())
+    }
+
+    /// The axis (0 for x, 1 for y and 2 for z) along which the box is the longest.
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+
+        (1..3).fold(0, |longest, axis| {
+            if extent[axis] > extent[longest] {
+                axis
+            } else {
+                longest
+            }
+        })
+    }
+
+    /// Whether the `ray` passes through the box between `t_min` and `t_max`, using
+    /// the slab test.
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+
+        for axis in 0..3 {
+            // rays parallel to the slab are either always or never within it
+            if ray.direction[axis] == 0.0 {
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return false;
+                }
+                continue;
+            }
+
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+
+            if t_max < t_min {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cube() -> Aabb {
+        Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn it_hits_the_box() {
+        let aabb = unit_cube();
+
+        let through = Ray::new(Vec3::new(0.5, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(aabb.hit(&through, 0.0, 10.0));
+        // the box is behind or beyond the interval
+        assert!(!aabb.hit(&through, 0.0, 3.0));
+        assert!(!aabb.hit(&through, 6.5, 10.0));
+
+        let diagonal = Ray::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        assert!(aabb.hit(&diagonal, 0.0, 10.0));
+
+        let beside = Ray::new(Vec3::new(1.5, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!aabb.hit(&beside, 0.0, 10.0));
+    }
+
+    #[test]
+    fn it_hits_flat_boxes() {
+        let flat = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(flat.hit(&ray, 0.0, 10.0));
+
+        // parallel to the box and starting on its boundary
+        let along = Ray::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(flat.hit(&along, 0.0, 10.0));
+    }
+
+    #[test]
+    fn empty_box_is_never_hit() {
+        let empty = Aabb::empty();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+
+        assert!(empty.is_empty());
+        assert!(!empty.hit(&ray, 0.0, f64::MAX));
+        assert_eq!(0.0, empty.surface_area());
+        assert_eq!(unit_cube(), empty.union(&unit_cube()));
+    }
+
+    #[test]
+    fn it_measures_the_box() {
+        let aabb = Aabb::from_points(vec![
+            Vec3::new(1.0, 0.0, -1.0),
+            Vec3::new(-1.0, 4.0, 0.0),
+            Vec3::new(0.0, 2.0, 1.0),
+        ]);
+
+        assert_eq!(Vec3::new(-1.0, 0.0, -1.0), aabb.min);
+        assert_eq!(Vec3::new(1.0, 4.0, 1.0), aabb.max);
+        assert_eq!(Vec3::new(0.0, 2.0, 0.0), aabb.centroid());
+        assert_eq!(2.0 * (8.0 + 8.0 + 4.0), aabb.surface_area());
+        assert_eq!(1, aabb.longest_axis());
+    }
+}
@@ -1,94 +1,72 @@
-use std::f64;
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::LineWriter;
-use std::ops::Div;
+use std::sync::Arc;
 
-use rand::{thread_rng, Rng};
-
-use ray_trace::hitable::Hitable;
+use ray_trace::bvh::Bvh;
+use ray_trace::environment::Environment;
+use ray_trace::environment::EnvironmentMap;
+use ray_trace::environment::Gradient;
 use ray_trace::hitable::HitableList;
+use ray_trace::material::Dielectric;
+use ray_trace::material::Lambertian;
+use ray_trace::material::Metal;
 use ray_trace::object::Sphere;
+use ray_trace::render::PathTracer;
+use ray_trace::render::Renderer;
+use ray_trace::render::Settings;
 use ray_trace::Camera;
-use ray_trace::Ray;
 use ray_trace::Vec3;
 
 fn main() -> Result<(), std::io::Error> {
-    let width = 200;
-    let height = 100;
-    let samples_per_pixel = 100;
-    let max_color = 255;
-
-    let file = File::create("image.ppm")?;
-    let mut file = LineWriter::new(file);
-
-    let beginning_file = format!("P3\n{} {}\n{}\n", width, height, max_color);
-    file.write_all(beginning_file.as_bytes())?;
-
+    let settings = Settings {
+        width: 200,
+        height: 100,
+        samples_per_pixel: 100,
+        max_depth: 50,
+        tile_size: 16,
+        seed: 0,
+    };
+
+    let glass = Arc::new(Dielectric::new(1.5));
     let objects = vec![
-        Sphere::new(Vec3::new(0.0, 0.0, -1.0), 0.5),
-        Sphere::new(Vec3::new(0.0, -100.5, -1.0), 100.0),
+        Sphere::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Lambertian::new(Vec3::new(0.1, 0.2, 0.5))),
+        ),
+        Sphere::new(
+            Vec3::new(0.0, -100.5, -1.0),
+            100.0,
+            Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.0))),
+        ),
+        Sphere::new(
+            Vec3::new(1.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.3)),
+        ),
+        Sphere::new(Vec3::new(-1.0, 0.0, -1.0), 0.5, glass.clone()),
+        // negative radius flips the normals and makes the glass sphere hollow
+        Sphere::new(Vec3::new(-1.0, 0.0, -1.0), -0.45, glass),
     ];
-    let world = HitableList::from(&objects);
-    let camera = Camera::default();
-    let mut rng = thread_rng();
-
-    for j in (0..height).rev() {
-        for i in 0..width {
-            let col = (0..samples_per_pixel)
-                .fold(Vec3::new(0.0, 0.0, 0.0), |mut col, _| {
-                    let u = (f64::from(i) + rng.gen::<f64>()) / f64::from(width);
-                    let v = (f64::from(j) + rng.gen::<f64>()) / f64::from(height);
-
-                    let ray = camera.get_ray(u, v);
-                    let _p = ray.point_at_parameter(2.0);
-
-                    col += color(&ray, &world);
-                    col
-                })
-                .div(f64::from(samples_per_pixel))
-                // Add `Gamma: 2` for fixing the color
-                .sqrt();
-
-            let ir = (255.99 * col[0]) as i32;
-            let ig = (255.99 * col[1]) as i32;
-            let ib = (255.99 * col[2]) as i32;
-
-            let pixel_color = format!("{} {} {}\n", ir, ig, ib);
-            file.write_all(pixel_color.as_bytes())?;
-        }
-    }
+    let world = Bvh::new(HitableList::from(objects));
+    // an optional Radiance `.hdr` environment map replaces the sky
+    let environment: Box<dyn Environment> = match std::env::args().nth(1) {
+        Some(path) => Box::new(EnvironmentMap::open(path)?),
+        None => Box::new(Gradient::default()),
+    };
+    let look_from = Vec3::new(-2.0, 2.0, 1.0);
+    let look_at = Vec3::new(0.0, 0.0, -1.0);
+    let camera = Camera::look_at(
+        look_from,
+        look_at,
+        Vec3::new(0.0, 1.0, 0.0),
+        50.0,
+        settings.width as f64 / settings.height as f64,
+    )
+    .with_lens(0.1, (look_from - look_at).length());
+
+    let renderer = Renderer::new(settings, PathTracer::new(environment));
+    let image = renderer.render(&world, &camera);
+
+    image.save("image.png")?;
 
     Ok(())
 }
-
-fn color<T: Hitable>(ray: &Ray, world: &HitableList<T>) -> Vec3 {
-    let vec3_1_1_1 = Vec3::new(1.0, 1.0, 1.0);
-
-    match world.hit(ray, 0.001, f64::MAX) {
-        Some(hit_record) => {
-            let target = hit_record.p + hit_record.normal + random_in_unit_sphere();
-
-            0.5 * color(&Ray::new(hit_record.p, target - hit_record.p), world)
-        }
-        None => {
-            let unit_direction = Vec3::unit_vector(ray.direction);
-            let t = 0.5 * (unit_direction.y() + 1.0);
-
-            (1.0 - t) * vec3_1_1_1 + t * Vec3::new(0.5, 0.7, 1.0)
-        }
-    }
-}
-
-fn random_in_unit_sphere() -> Vec3 {
-    let mut rng = thread_rng();
-    let mut p: Vec3;
-    loop {
-        let random_vec3 = Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>());
-        p = 2.0 * random_vec3 - Vec3::new(1.0, 1.0, 1.0);
-        if p.squared_length() < 1.0 {
-            break;
-        }
-    }
-    p
-}
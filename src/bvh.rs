@@ -0,0 +1,448 @@
+//! Bounding volume hierarchy, which only tests a ray against the hitables whose
+//! bounding boxes it passes through.
+
+use crate::aabb::Aabb;
+use crate::hitable::{HitRecord, Hitable, HitableList};
+use crate::Ray;
+use crate::Vec3;
+
+/// The number of candidate splits along an axis evaluated with the SAH.
+const BINS: usize = 16;
+/// Leaves with more primitives are always split when possible.
+const MAX_LEAF_SIZE: usize = 4;
+/// The cost of testing a box relative to the cost of testing a primitive.
+const TRAVERSAL_COST: f64 = 0.5;
+
+/// An owned scene like the `HitableList`, where every hit takes time logarithmic
+/// in the number of hitables instead of linear.
+pub struct Bvh {
+    hitables: Vec<Box<dyn Hitable>>,
+    tree: Tree,
+    /// The hitables without a bounding box, which are tested for every ray.
+    unbounded: Vec<Box<dyn Hitable>>,
+}
+
+impl Bvh {
+    pub fn new(list: HitableList) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = list
+            .into_iter()
+            .map(|hitable| (hitable.bounding_box(), hitable))
+            .partition(|(bounds, _)| bounds.is_some());
+
+        let bounds: Vec<Aabb> = bounded.iter().filter_map(|(bounds, _)| *bounds).collect();
+
+        Self {
+            hitables: bounded.into_iter().map(|(_, hitable)| hitable).collect(),
+            tree: Tree::new(&bounds),
+            unbounded: unbounded.into_iter().map(|(_, hitable)| hitable).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hitables.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<HitableList> for Bvh {
+    fn from(list: HitableList) -> Self {
+        Self::new(list)
+    }
+}
+
+impl Hitable for Bvh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let closest = self.tree.hit(ray, t_min, t_max, |index, closest_so_far| {
+            self.hitables[index].hit(ray, t_min, closest_so_far)
+        });
+
+        self.unbounded.iter().fold(closest, |closest, hitable| {
+            let closest_so_far = closest.as_ref().map_or(t_max, |hr| hr.t);
+
+            hitable.hit(ray, t_min, closest_so_far).or(closest)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            Some(self.tree.bounding_box())
+        } else {
+            None
+        }
+    }
+}
+
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        /// The range of the primitives in the `indices` of the tree.
+        start: usize,
+        count: usize,
+    },
+    /// The first child directly follows its parent.
+    Interior {
+        bounds: Aabb,
+        second_child: usize,
+        axis: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// The hierarchy over primitives given by their bounding boxes, which leaves
+/// hitting the primitives themselves to its owner.
+pub(crate) struct Tree {
+    nodes: Vec<Node>,
+    /// The primitives ordered such that every leaf has a contiguous range.
+    indices: Vec<usize>,
+}
+
+impl Tree {
+    /// Builds the tree top down, splitting the primitives where the surface area
+    /// heuristic (SAH) estimates the cheapest traversal.
+    pub(crate) fn new(bounds: &[Aabb]) -> Self {
+        let mut tree = Self {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: (0..bounds.len()).collect(),
+        };
+
+        if !bounds.is_empty() {
+            let centroids: Vec<Vec3> = bounds.iter().map(Aabb::centroid).collect();
+            tree.build(bounds, &centroids, 0, bounds.len());
+        }
+
+        tree
+    }
+
+    pub(crate) fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or_else(Aabb::empty, |root| *root.bounds())
+    }
+
+    /// Finds the closest hit, where `hit` is called with the index of a primitive
+    /// and the closest hit so far.
+    pub(crate) fn hit<'a, F>(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        mut hit: F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f64) -> Option<HitRecord<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        self.hit_node(0, ray, t_min, t_max, &mut hit)
+    }
+
+    fn hit_node<'a, F>(
+        &self,
+        node: usize,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        hit: &mut F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f64) -> Option<HitRecord<'a>>,
+    {
+        if !self.nodes[node].bounds().hit(ray, t_min, t_max) {
+            return None;
+        }
+
+        match self.nodes[node] {
+            Node::Leaf { start, count, .. } => {
+                self.indices[start..start + count]
+                    .iter()
+                    .fold(None, |closest, &index| {
+                        let closest_so_far = closest.as_ref().map_or(t_max, |hr: &HitRecord| hr.t);
+
+                        hit(index, closest_so_far).or(closest)
+                    })
+            }
+            Node::Interior {
+                second_child, axis, ..
+            } => {
+                // the nearer child first, so the farther one can be skipped when
+                // it's entirely behind the closest hit
+                let (near, far) = if ray.direction[axis] < 0.0 {
+                    (second_child, node + 1)
+                } else {
+                    (node + 1, second_child)
+                };
+
+                let near_hit = self.hit_node(near, ray, t_min, t_max, hit);
+                let closest_so_far = near_hit.as_ref().map_or(t_max, |hr| hr.t);
+
+                self.hit_node(far, ray, t_min, closest_so_far, hit)
+                    .or(near_hit)
+            }
+        }
+    }
+
+    /// Adds the node for the primitives in `indices[start..end]` and its
+    /// descendants.
+    fn build(&mut self, bounds: &[Aabb], centroids: &[Vec3], start: usize, end: usize) {
+        let node = self.nodes.len();
+        let indices = &mut self.indices[start..end];
+        let node_bounds = indices
+            .iter()
+            .fold(Aabb::empty(), |aabb, &index| aabb.union(&bounds[index]));
+        let leaf = Node::Leaf {
+            bounds: node_bounds,
+            start,
+            count: end - start,
+        };
+
+        let split = best_split(indices, bounds, centroids, &node_bounds);
+        let (axis, mid) = match split {
+            Some((axis, bin, centroid_bounds, cost))
+                if cost < indices.len() as f64 || indices.len() > MAX_LEAF_SIZE =>
+            {
+                let mid = partition(indices, |index| {
+                    bin_of(centroids[index], &centroid_bounds, axis) < bin
+                });
+                (axis, start + mid)
+            }
+            _ => {
+                self.nodes.push(leaf);
+                return;
+            }
+        };
+
+        self.nodes.push(Node::Interior {
+            bounds: node_bounds,
+            second_child: 0,
+            axis,
+        });
+        self.build(bounds, centroids, start, mid);
+        let second = self.nodes.len();
+        self.build(bounds, centroids, mid, end);
+
+        if let Node::Interior { second_child, .. } = &mut self.nodes[node] {
+            *second_child = second;
+        }
+    }
+}
+
+/// The axis, the first bin of the second child, the bounds of the centroids and
+/// the estimated cost of the cheapest split, if the primitives can be split at all.
+fn best_split(
+    indices: &[usize],
+    bounds: &[Aabb],
+    centroids: &[Vec3],
+    node_bounds: &Aabb,
+) -> Option<(usize, usize, Aabb, f64)> {
+    let centroid_bounds = Aabb::from_points(indices.iter().map(|&index| centroids[index]));
+    let axis = centroid_bounds.longest_axis();
+    // all of the centroids are at the same spot
+    if centroid_bounds.max[axis] <= centroid_bounds.min[axis] {
+        return None;
+    }
+
+    let mut bins = [(Aabb::empty(), 0); BINS];
+    for &index in indices {
+        let bin = &mut bins[bin_of(centroids[index], &centroid_bounds, axis)];
+        bin.0 = bin.0.union(&bounds[index]);
+        bin.1 += 1;
+    }
+
+    // the area weighted counts of the primitives left of each split
+    let mut left = [0.0; BINS];
+    let mut aabb = Aabb::empty();
+    let mut count = 0;
+    for bin in 1..BINS {
+        aabb = aabb.union(&bins[bin - 1].0);
+        count += bins[bin - 1].1;
+        left[bin] = if count == 0 {
+            f64::INFINITY
+        } else {
+            aabb.surface_area() * count as f64
+        };
+    }
+
+    let mut best: Option<(usize, f64)> = None;
+    let mut aabb = Aabb::empty();
+    let mut count = 0;
+    for bin in (1..BINS).rev() {
+        aabb = aabb.union(&bins[bin].0);
+        count += bins[bin].1;
+        if count == 0 || left[bin].is_infinite() {
+            continue;
+        }
+
+        let cost = TRAVERSAL_COST
+            + (left[bin] + aabb.surface_area() * count as f64) / node_bounds.surface_area();
+        if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+            best = Some((bin, cost));
+        }
+    }
+
+    best.map(|(bin, cost)| (axis, bin, centroid_bounds, cost))
+}
+
+fn bin_of(centroid: Vec3, centroid_bounds: &Aabb, axis: usize) -> usize {
+    let offset = (centroid[axis] - centroid_bounds.min[axis])
+        / (centroid_bounds.max[axis] - centroid_bounds.min[axis]);
+
+    ((offset * BINS as f64) as usize).min(BINS - 1)
+}
+
+/// Moves the indices matching the `predicate` to the front, returning how many
+/// there are.
+fn partition<P: Fn(usize) -> bool>(indices: &mut [usize], predicate: P) -> usize {
+    let mut mid = 0;
+    for i in 0..indices.len() {
+        if predicate(indices[i]) {
+            indices.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    mid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::object::{Sphere, Triangle};
+    use rand::Rng;
+    use rand_pcg::Pcg32;
+    use std::sync::Arc;
+
+    /// A plane which has no bounding box.
+    struct Floor(Lambertian);
+
+    impl Hitable for Floor {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+            let t = -ray.origin.y() / ray.direction.y();
+            if t <= t_min || t >= t_max {
+                return None;
+            }
+
+            Some(HitRecord::new(
+                t,
+                ray.point_at_parameter(t),
+                Vec3::new(0.0, 1.0, 0.0),
+                &self.0,
+            ))
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            None
+        }
+    }
+
+    fn random_vec3(rng: &mut Pcg32, scale: f64) -> Vec3 {
+        Vec3::new(
+            rng.gen_range(-scale, scale),
+            rng.gen_range(-scale, scale),
+            rng.gen_range(-scale, scale),
+        )
+    }
+
+    /// Spheres and triangles of different sizes scattered around the origin.
+    fn random_scene(rng: &mut Pcg32) -> HitableList {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let mut list = HitableList::new();
+
+        for _ in 0..200 {
+            let center = random_vec3(rng, 10.0);
+            list.push(Sphere::new(
+                center,
+                rng.gen_range(0.1, 1.0),
+                material.clone(),
+            ));
+
+            let corner = random_vec3(rng, 10.0);
+            list.push(Triangle::new(
+                [
+                    corner,
+                    corner + random_vec3(rng, 2.0),
+                    corner + random_vec3(rng, 2.0),
+                ],
+                material.clone(),
+            ));
+        }
+
+        list
+    }
+
+    #[test]
+    fn it_finds_the_same_hits_as_a_list() {
+        let mut rng = Pcg32::new(1, 2);
+        let list = random_scene(&mut rng);
+        let bvh = Bvh::new(random_scene(&mut Pcg32::new(1, 2)));
+        assert_eq!(list.len(), bvh.len());
+        assert_eq!(list.bounding_box(), bvh.bounding_box());
+
+        for _ in 0..1000 {
+            let ray = Ray::new(random_vec3(&mut rng, 12.0), random_vec3(&mut rng, 1.0));
+
+            let expected = list.hit(&ray, 0.001, f64::MAX).map(|hr| (hr.t, hr.p));
+            let actual = bvh.hit(&ray, 0.001, f64::MAX).map(|hr| (hr.t, hr.p));
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn it_tests_unbounded_hitables_for_every_ray() {
+        let mut list = random_scene(&mut Pcg32::new(3, 4));
+        list.push(Floor(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))));
+        let bvh = Bvh::new(list);
+        assert_eq!(None, bvh.bounding_box());
+
+        // straight down from high above all of the other hitables
+        let ray = Ray::new(Vec3::new(50.0, 50.0, 50.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit_record = bvh.hit(&ray, 0.001, f64::MAX).expect("Should hit");
+        assert_eq!(50.0, hit_record.t);
+    }
+
+    #[test]
+    fn empty_bvh_is_never_hit() {
+        let bvh = Bvh::new(HitableList::new());
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(bvh.is_empty());
+        assert!(bvh.hit(&ray, 0.001, f64::MAX).is_none());
+        assert_eq!(Some(Aabb::empty()), bvh.bounding_box());
+    }
+
+    #[test]
+    fn tree_keeps_small_leaves() {
+        let bounds: Vec<Aabb> = (0..1000)
+            .map(|i| {
+                let min = Vec3::new(i as f64, 0.0, 0.0);
+                Aabb::new(min, min + Vec3::new(0.5, 0.5, 0.5))
+            })
+            .collect();
+
+        let tree = Tree::new(&bounds);
+
+        let mut indices = tree.indices.clone();
+        indices.sort_unstable();
+        assert_eq!((0..1000).collect::<Vec<_>>(), indices);
+        assert!(tree.nodes.iter().all(|node| match node {
+            Node::Leaf { count, .. } => *count <= MAX_LEAF_SIZE,
+            Node::Interior { .. } => true,
+        }));
+        assert_eq!(
+            Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(999.5, 0.5, 0.5)),
+            tree.bounding_box()
+        );
+    }
+}
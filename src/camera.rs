@@ -1,49 +1,577 @@
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+
 use crate::Ray;
 use crate::Vec3;
 
+/// How the camera maps the image onto rays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Pinhole or thin lens camera.
+    Perspective,
+    /// Parallel rays, objects keep their size regardless of the distance.
+    Orthographic,
+    /// Equidistant fisheye, the angle from the view direction grows linearly with
+    /// the distance from the center of the image. The `fov` (in radians) spans
+    /// from the bottom to the top of the image, outside of the image circle the
+    /// image stays black.
+    Fisheye { fov: f64, aspect: f64 },
+    /// Full 360° by 180° latitude/longitude panorama.
+    Equirectangular,
+}
+
 pub struct Camera {
     origin: Vec3,
     lower_left_corner: Vec3,
     horizontal: Vec3,
     vertical: Vec3,
+    /// Orthonormal basis, the camera looks towards -w.
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    /// Zero for a pinhole camera which keeps everything in focus.
+    lens_radius: f64,
+    projection: Projection,
+    /// The interval during which the rays are shot, equal for still images.
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
     pub fn new(origin: Vec3, lower_left_corner: Vec3, horizontal: Vec3, vertical: Vec3) -> Self {
+        let u = Vec3::unit_vector(horizontal);
+        let v = Vec3::unit_vector(vertical);
+
         Self {
             origin,
             lower_left_corner,
             vertical,
             horizontal,
+            u,
+            v,
+            w: Vec3::cross(u, v),
+            lens_radius: 0.0,
+            projection: Projection::Perspective,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    /// Creates a camera at `look_from` pointed at `look_at`, where `view_up` is the
+    /// direction which appears upwards in the image.
+    ///
+    /// `vertical_fov` is the angle (in degrees) from the bottom to the top of the
+    /// image and `aspect` is its width divided by its height.
+    pub fn look_at(
+        look_from: Vec3,
+        look_at: Vec3,
+        view_up: Vec3,
+        vertical_fov: f64,
+        aspect: f64,
+    ) -> Self {
+        let half_height = (vertical_fov.to_radians() / 2.0).tan();
+        let half_width = aspect * half_height;
+
+        let (u, v, w) = basis(look_from, look_at, view_up);
+
+        Self::new(
+            look_from,
+            look_from - half_width * u - half_height * v - w,
+            2.0 * half_width * u,
+            2.0 * half_height * v,
+        )
+    }
+
+    /// Creates an orthographic camera at `look_from` pointed at `look_at`, where
+    /// `height` is the size of the area seen from the bottom to the top of the image.
+    pub fn orthographic(
+        look_from: Vec3,
+        look_at: Vec3,
+        view_up: Vec3,
+        height: f64,
+        aspect: f64,
+    ) -> Self {
+        let (u, v, w) = basis(look_from, look_at, view_up);
+        let horizontal = aspect * height * u;
+        let vertical = height * v;
+
+        Self {
+            origin: look_from,
+            // the image plane goes through the camera position
+            lower_left_corner: look_from - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            lens_radius: 0.0,
+            projection: Projection::Orthographic,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let direction =
-            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin;
+    /// Creates an equidistant fisheye camera at `look_from` pointed at `look_at`,
+    /// where `fov` is the angle (in degrees) from the bottom to the top of the image.
+    pub fn fisheye(look_from: Vec3, look_at: Vec3, view_up: Vec3, fov: f64, aspect: f64) -> Self {
+        Self {
+            projection: Projection::Fisheye {
+                fov: fov.to_radians(),
+                aspect,
+            },
+            ..Self::look_at(look_from, look_at, view_up, 90.0, aspect)
+        }
+    }
 
-        Ray {
-            origin: self.origin,
-            direction,
+    /// Creates a 360° panorama camera at `look_from`, with `look_at` in the center
+    /// of the image and `view_up` at its top edge.
+    pub fn equirectangular(look_from: Vec3, look_at: Vec3, view_up: Vec3) -> Self {
+        Self {
+            projection: Projection::Equirectangular,
+            ..Self::look_at(look_from, look_at, view_up, 90.0, 2.0)
         }
     }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Turns the camera into a thin lens camera with the given `aperture` (the
+    /// diameter of the lens), which only keeps the objects at `focus_distance`
+    /// in sharp focus.
+    ///
+    /// # Panics
+    ///
+    /// If the camera doesn't use `Projection::Perspective`, only perspective
+    /// cameras have a lens.
+    pub fn with_lens(mut self, aperture: f64, focus_distance: f64) -> Self {
+        assert_eq!(
+            Projection::Perspective,
+            self.projection,
+            "Only perspective cameras have a lens"
+        );
+
+        let center = self.lower_left_corner + self.horizontal / 2.0 + self.vertical / 2.0;
+        let distance = Vec3::dot(self.origin - center, self.w);
+        // moves the image plane onto the plane in focus, keeping the field of view
+        let scale = focus_distance / distance;
+
+        self.lower_left_corner = self.origin + (self.lower_left_corner - self.origin) * scale;
+        self.horizontal *= scale;
+        self.vertical *= scale;
+        self.lens_radius = aperture / 2.0;
+
+        self
+    }
+
+    /// Opens the shutter from `open` until `close`, every ray being shot at a
+    /// random time in between to blur the objects moving in the meantime.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    /// The times the shutter opens and closes.
+    pub fn shutter(&self) -> (f64, f64) {
+        (self.shutter_open, self.shutter_close)
+    }
+
+    /// Returns the ray through the point of the image at `u` from the left and
+    /// `v` from the bottom edge, or `None` if the projection doesn't cover that
+    /// point, like the corners outside of the fisheye image circle.
+    pub fn get_ray(&self, u: f64, v: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        let ray = self.ray_through(u, v, rng)?;
+
+        Some(if self.shutter_close > self.shutter_open {
+            let time =
+                self.shutter_open + rng.gen::<f64>() * (self.shutter_close - self.shutter_open);
+
+            ray.with_time(time)
+        } else {
+            ray.with_time(self.shutter_open)
+        })
+    }
+
+    fn ray_through(&self, u: f64, v: f64, rng: &mut dyn RngCore) -> Option<Ray> {
+        Some(match self.projection {
+            Projection::Perspective => {
+                let offset = if self.lens_radius > 0.0 {
+                    let lens = self.lens_radius * random_in_unit_disk(rng);
+
+                    self.u * lens.x() + self.v * lens.y()
+                } else {
+                    Vec3::new(0.0, 0.0, 0.0)
+                };
+                let origin = self.origin + offset;
+                let direction =
+                    self.lower_left_corner + u * self.horizontal + v * self.vertical - origin;
+
+                Ray::new(origin, direction)
+            }
+            Projection::Orthographic => Ray::new(
+                self.lower_left_corner + u * self.horizontal + v * self.vertical,
+                -self.w,
+            ),
+            Projection::Fisheye { fov, aspect } => {
+                let x = (2.0 * u - 1.0) * aspect;
+                let y = 2.0 * v - 1.0;
+                let radius = (x * x + y * y).sqrt();
+                // the image circle touches the top and bottom edges of the image
+                if radius > 1.0 {
+                    return None;
+                }
+                let theta = radius * fov / 2.0;
+                let phi = y.atan2(x);
+
+                self.ray_at_angles(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+            }
+            Projection::Equirectangular => {
+                let longitude = (u - 0.5) * 2.0 * PI;
+                let latitude = (v - 0.5) * PI;
+
+                self.ray_at_angles(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                )
+            }
+        })
+    }
+
+    /// The ray from the camera position in the direction given in the camera basis.
+    fn ray_at_angles(&self, right: f64, up: f64, forward: f64) -> Ray {
+        Ray::new(self.origin, right * self.u + up * self.v - forward * self.w)
+    }
 }
 
 impl Default for Camera {
     fn default() -> Self {
-        Self {
-            origin: Vec3::new(0.0, 0.0, 0.0),
-            lower_left_corner: Vec3::new(-2.0, -1.0, -1.0),
-            vertical: Vec3::new(0.0, 2.0, 0.0),
-            horizontal: Vec3::new(4.0, 0.0, 0.0),
+        Self::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(-2.0, -1.0, -1.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        )
+    }
+}
+
+/// Orthonormal basis of a camera at `look_from` looking at `look_at`, the camera
+/// looks towards -w.
+fn basis(look_from: Vec3, look_at: Vec3, view_up: Vec3) -> (Vec3, Vec3, Vec3) {
+    let w = Vec3::unit_vector(look_from - look_at);
+    let u = Vec3::unit_vector(Vec3::cross(view_up, w));
+    let v = Vec3::cross(w, u);
+
+    (u, v, w)
+}
+
+fn random_in_unit_disk(rng: &mut dyn RngCore) -> Vec3 {
+    loop {
+        let p = Vec3::new(
+            2.0 * rng.gen::<f64>() - 1.0,
+            2.0 * rng.gen::<f64>() - 1.0,
+            0.0,
+        );
+        if p.squared_length() < 1.0 {
+            return p;
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::testing::assert_vec3_eq;
+
     #[test]
     fn it_can_get_a_ray_at_a_point() {
-        // TODO: Implement for get_ray()
+        let mut rng = thread_rng();
+        let camera = Camera::default();
+
+        let center = camera
+            .get_ray(0.5, 0.5, &mut rng)
+            .expect("Should be in the image");
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0), center.origin);
+        assert_eq!(Vec3::new(0.0, 0.0, -1.0), center.direction);
+
+        let lower_left = camera
+            .get_ray(0.0, 0.0, &mut rng)
+            .expect("Should be in the image");
+        assert_eq!(Vec3::new(-2.0, -1.0, -1.0), lower_left.direction);
+
+        let upper_right = camera
+            .get_ray(1.0, 1.0, &mut rng)
+            .expect("Should be in the image");
+        assert_eq!(Vec3::new(2.0, 1.0, -1.0), upper_right.direction);
+    }
+
+    #[test]
+    fn look_at_matches_the_default_camera() {
+        let mut rng = thread_rng();
+        let camera = Camera::look_at(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+        );
+        let default = Camera::default();
+
+        for &(u, v) in &[(0.0, 0.0), (0.5, 0.5), (1.0, 0.25), (0.3, 1.0)] {
+            assert_vec3_eq(
+                default
+                    .get_ray(u, v, &mut rng)
+                    .expect("Should be in the image")
+                    .origin,
+                camera
+                    .get_ray(u, v, &mut rng)
+                    .expect("Should be in the image")
+                    .origin,
+            );
+            assert_vec3_eq(
+                default
+                    .get_ray(u, v, &mut rng)
+                    .expect("Should be in the image")
+                    .direction,
+                camera
+                    .get_ray(u, v, &mut rng)
+                    .expect("Should be in the image")
+                    .direction,
+            );
+        }
+    }
+
+    #[test]
+    fn look_at_points_the_camera_at_the_target() {
+        let mut rng = thread_rng();
+        let look_from = Vec3::new(3.0, 3.0, 2.0);
+        let look_at = Vec3::new(0.0, 0.0, -1.0);
+        let camera = Camera::look_at(look_from, look_at, Vec3::new(0.0, 1.0, 0.0), 40.0, 1.5);
+
+        let center = camera
+            .get_ray(0.5, 0.5, &mut rng)
+            .expect("Should be in the image");
+        assert_eq!(look_from, center.origin);
+        assert_vec3_eq(
+            Vec3::unit_vector(look_at - look_from),
+            Vec3::unit_vector(center.direction),
+        );
+
+        // the top edge is 20 degrees above the center
+        let top = camera
+            .get_ray(0.5, 1.0, &mut rng)
+            .expect("Should be in the image");
+        let cosine = Vec3::dot(
+            Vec3::unit_vector(top.direction),
+            Vec3::unit_vector(center.direction),
+        );
+        assert!((cosine - 20.0_f64.to_radians().cos()).abs() < 1e-10);
+        // and the up direction stays up
+        assert!(top.direction.y() > center.direction.y());
+    }
+
+    #[test]
+    fn thin_lens_keeps_the_focus_plane_sharp() {
+        let mut rng = thread_rng();
+        let camera = Camera::look_at(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+        )
+        .with_lens(0.5, 4.0);
+
+        let mut blurred = false;
+        for _ in 0..20 {
+            let ray = camera
+                .get_ray(0.75, 0.25, &mut rng)
+                .expect("Should be in the image");
+
+            // the rays start anywhere on the lens
+            assert!(ray.origin.length() <= 0.25);
+            assert_eq!(0.0, ray.origin.z());
+            blurred |= ray.origin.length() > 0.0;
+
+            // but all of them meet on the plane in focus
+            let t = -4.0 / ray.direction.z();
+            assert_vec3_eq(Vec3::new(4.0, -2.0, -4.0), ray.point_at_parameter(t));
+        }
+        assert!(blurred);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only perspective cameras have a lens")]
+    fn it_panics_on_lenses_for_other_projections() {
+        Camera::fisheye(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            180.0,
+            1.0,
+        )
+        .with_lens(0.5, 4.0);
+    }
+
+    #[test]
+    fn orthographic_camera_shoots_parallel_rays() {
+        let mut rng = thread_rng();
+        let camera = Camera::orthographic(
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            4.0,
+            2.0,
+        );
+
+        let lower_left = camera
+            .get_ray(0.0, 0.0, &mut rng)
+            .expect("Should be in the image");
+        assert_vec3_eq(Vec3::new(-4.0, -2.0, 5.0), lower_left.origin);
+        assert_vec3_eq(Vec3::new(0.0, 0.0, -1.0), lower_left.direction);
+
+        let upper_right = camera
+            .get_ray(1.0, 1.0, &mut rng)
+            .expect("Should be in the image");
+        assert_vec3_eq(Vec3::new(4.0, 2.0, 5.0), upper_right.origin);
+        assert_vec3_eq(Vec3::new(0.0, 0.0, -1.0), upper_right.direction);
+        assert_eq!(Projection::Orthographic, camera.projection());
+    }
+
+    #[test]
+    fn fisheye_camera_spreads_angles_evenly() {
+        let mut rng = thread_rng();
+        let camera = Camera::fisheye(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            180.0,
+            1.0,
+        );
+
+        let center = camera
+            .get_ray(0.5, 0.5, &mut rng)
+            .expect("Should be in the image");
+        assert_vec3_eq(Vec3::new(0.0, 0.0, -1.0), center.direction);
+
+        // 45 degrees half way to the top and 90 degrees at the top edge
+        let half_way = Vec3::unit_vector(Vec3::new(0.0, 1.0, -1.0));
+        assert_vec3_eq(
+            half_way,
+            camera
+                .get_ray(0.5, 0.75, &mut rng)
+                .expect("Should be in the image")
+                .direction,
+        );
+        assert_vec3_eq(
+            Vec3::new(0.0, 1.0, 0.0),
+            camera
+                .get_ray(0.5, 1.0, &mut rng)
+                .expect("Should be in the image")
+                .direction,
+        );
+        assert_vec3_eq(
+            Vec3::new(-1.0, 0.0, 0.0),
+            camera
+                .get_ray(0.0, 0.5, &mut rng)
+                .expect("Should be in the image")
+                .direction,
+        );
+
+        // the corners are outside of the image circle
+        assert!(camera.get_ray(1.0, 1.0, &mut rng).is_none());
+        assert!(camera.get_ray(0.05, 0.1, &mut rng).is_none());
+    }
+
+    #[test]
+    fn equirectangular_camera_sees_all_around() {
+        let mut rng = thread_rng();
+        let camera = Camera::equirectangular(
+            Vec3::new(1.0, 2.0, 3.0),
+            Vec3::new(1.0, 2.0, 2.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+
+        let center = camera
+            .get_ray(0.5, 0.5, &mut rng)
+            .expect("Should be in the image");
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0), center.origin);
+        assert_vec3_eq(Vec3::new(0.0, 0.0, -1.0), center.direction);
+
+        assert_vec3_eq(
+            Vec3::new(1.0, 0.0, 0.0),
+            camera
+                .get_ray(0.75, 0.5, &mut rng)
+                .expect("Should be in the image")
+                .direction,
+        );
+        assert_vec3_eq(
+            Vec3::new(0.0, 0.0, 1.0),
+            camera
+                .get_ray(1.0, 0.5, &mut rng)
+                .expect("Should be in the image")
+                .direction,
+        );
+        assert_vec3_eq(
+            Vec3::new(0.0, 1.0, 0.0),
+            camera
+                .get_ray(0.3, 1.0, &mut rng)
+                .expect("Should be in the image")
+                .direction,
+        );
+        assert_vec3_eq(
+            Vec3::new(0.0, -1.0, 0.0),
+            camera
+                .get_ray(0.3, 0.0, &mut rng)
+                .expect("Should be in the image")
+                .direction,
+        );
+    }
+
+    #[test]
+    fn rays_are_shot_while_the_shutter_is_open() {
+        let mut rng = thread_rng();
+        let still = Camera::default();
+        assert_eq!((0.0, 0.0), still.shutter());
+        assert_eq!(
+            0.0,
+            still
+                .get_ray(0.5, 0.5, &mut rng)
+                .expect("Should be in the image")
+                .time
+        );
+
+        let camera = Camera::default().with_shutter(1.0, 1.5);
+        let times: Vec<f64> = (0..100)
+            .map(|_| {
+                camera
+                    .get_ray(0.5, 0.5, &mut rng)
+                    .expect("Should be in the image")
+                    .time
+            })
+            .collect();
+
+        assert!(times.iter().all(|time| (1.0..1.5).contains(time)));
+        assert!(times.iter().any(|&time| time != times[0]));
+    }
+
+    #[test]
+    fn it_picks_random_points_inside_the_unit_disk() {
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            let p = random_in_unit_disk(&mut rng);
+
+            assert!(p.squared_length() < 1.0);
+            assert_eq!(0.0, p.z());
+        }
     }
 }
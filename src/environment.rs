@@ -0,0 +1,389 @@
+use std::f64::consts::PI;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+
+use rand::{Rng, RngCore};
+
+use crate::image::Image;
+use crate::Vec3;
+
+/// A direction towards the environment picked by importance sampling.
+pub struct EnvironmentSample {
+    /// Unit vector pointing towards the environment.
+    pub direction: Vec3,
+    /// The light arriving from that direction.
+    pub radiance: Vec3,
+    /// The probability density (per solid angle) of picking the direction.
+    pub pdf: f64,
+}
+
+/// The light arriving from infinitely far away, used for rays which don't hit
+/// anything in the scene.
+pub trait Environment: Send + Sync {
+    fn radiance(&self, direction: Vec3) -> Vec3;
+
+    /// Picks a direction proportionally to the light coming from it, or `None`
+    /// if the environment can't be importance sampled.
+    fn sample(&self, _rng: &mut dyn RngCore) -> Option<EnvironmentSample> {
+        None
+    }
+
+    /// The probability density of `sample` picking the `direction`.
+    fn pdf(&self, _direction: Vec3) -> f64 {
+        0.0
+    }
+}
+
+/// The same light from every direction, e.g. black for indoor scenes.
+pub struct Constant {
+    pub color: Vec3,
+}
+
+impl Constant {
+    pub fn new(color: Vec3) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for Constant {
+    fn radiance(&self, _direction: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+/// Blends from the `bottom` color straight down to the `top` color straight up.
+pub struct Gradient {
+    pub bottom: Vec3,
+    pub top: Vec3,
+}
+
+impl Gradient {
+    pub fn new(bottom: Vec3, top: Vec3) -> Self {
+        Self { bottom, top }
+    }
+}
+
+impl Default for Gradient {
+    /// The white to light blue sky.
+    fn default() -> Self {
+        Self {
+            bottom: Vec3::new(1.0, 1.0, 1.0),
+            top: Vec3::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        let unit_direction = Vec3::unit_vector(direction);
+        let t = 0.5 * (unit_direction.y() + 1.0);
+
+        (1.0 - t) * self.bottom + t * self.top
+    }
+}
+
+/// An equirectangular (latitude/longitude) image of the surroundings.
+///
+/// The center of the image is straight ahead (-Z) and the top row is straight up (+Y).
+/// Directions are importance sampled proportionally to the brightness of the texels,
+/// so small and bright light sources like the sun are found quickly.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+    rows: Distribution,
+    columns: Vec<Distribution>,
+}
+
+impl EnvironmentMap {
+    /// Creates the map from `width * height` pixels in scanline order, starting
+    /// from the top left corner.
+    ///
+    /// # Panics
+    ///
+    /// If the map is empty or the number of `pixels` doesn't match its size.
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert!(width > 0 && height > 0, "Empty environment map");
+        assert_eq!(
+            Some(pixels.len()),
+            width.checked_mul(height),
+            "The pixels don't match the environment map size"
+        );
+
+        let columns: Vec<Distribution> = pixels
+            .chunks(width)
+            .enumerate()
+            .map(|(y, row)| {
+                // rows near the poles cover a smaller part of the sphere
+                let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+
+                Distribution::new(row.iter().map(|pixel| luminance(*pixel) * sin_theta))
+            })
+            .collect();
+        let rows = Distribution::new(columns.iter().map(|column| column.total));
+
+        Self {
+            width,
+            height,
+            pixels,
+            rows,
+            columns,
+        }
+    }
+
+    /// Loads the map from a Radiance `.hdr` file, or any other format `Image::open`
+    /// supports.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let image = Image::open(path)?;
+
+        if image.width() == 0 || image.height() == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Empty environment map"));
+        }
+
+        Ok(Self::new(
+            image.width(),
+            image.height(),
+            image.pixels().to_vec(),
+        ))
+    }
+
+    /// The density per solid angle of picking a direction with `sin_theta` inside
+    /// the texel `x`, `y`.
+    fn texel_pdf(&self, x: usize, y: usize, sin_theta: f64) -> f64 {
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        // the texel is sampled uniformly in `u` and `v`, and the solid angle of a
+        // small patch around the direction is `2 PI^2 sin(theta) du dv`
+        let texel_pdf = self.columns[y].values[x] / self.rows.total;
+
+        texel_pdf * (self.width * self.height) as f64 / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn texel(&self, direction: Vec3) -> (usize, usize) {
+        let direction = Vec3::unit_vector(direction);
+        let u = 0.5 + direction.x().atan2(-direction.z()) / (2.0 * PI);
+        let v = direction.y().clamp(-1.0, 1.0).acos() / PI;
+
+        (
+            ((u * self.width as f64) as usize).min(self.width - 1),
+            ((v * self.height as f64) as usize).min(self.height - 1),
+        )
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        let (x, y) = self.texel(direction);
+
+        self.pixels[y * self.width + x]
+    }
+
+    fn sample(&self, rng: &mut dyn RngCore) -> Option<EnvironmentSample> {
+        if self.rows.total <= 0.0 {
+            return None;
+        }
+
+        let y = self.rows.sample(rng.gen());
+        let x = self.columns[y].sample(rng.gen());
+
+        let u = (x as f64 + rng.gen::<f64>()) / self.width as f64;
+        let v = (y as f64 + rng.gen::<f64>()) / self.height as f64;
+        let phi = (u - 0.5) * 2.0 * PI;
+        let theta = v * PI;
+        let direction = Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+
+        Some(EnvironmentSample {
+            direction,
+            radiance: self.pixels[y * self.width + x],
+            pdf: self.texel_pdf(x, y, theta.sin()),
+        })
+    }
+
+    fn pdf(&self, direction: Vec3) -> f64 {
+        if self.rows.total <= 0.0 {
+            return 0.0;
+        }
+
+        let (x, y) = self.texel(direction);
+        let sin_theta = (1.0 - Vec3::unit_vector(direction).y().powi(2))
+            .max(0.0)
+            .sqrt();
+
+        self.texel_pdf(x, y, sin_theta)
+    }
+}
+
+/// Piecewise constant distribution over indices, proportional to their values.
+struct Distribution {
+    values: Vec<f64>,
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution {
+    fn new<I: IntoIterator<Item = f64>>(values: I) -> Self {
+        let values: Vec<f64> = values.into_iter().collect();
+        let mut total = 0.0;
+        let cdf = values
+            .iter()
+            .map(|value| {
+                total += value;
+                total
+            })
+            .collect();
+
+        Self { values, cdf, total }
+    }
+
+    /// Picks an index for the random number in `[0, 1)`.
+    fn sample(&self, random: f64) -> usize {
+        let target = random * self.total;
+
+        self.cdf
+            .partition_point(|&sum| sum <= target)
+            .min(self.cdf.len() - 1)
+    }
+}
+
+fn luminance(color: Vec3) -> f64 {
+    0.2126 * color.r() + 0.7152 * color.g() + 0.0722 * color.b()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+
+    #[test]
+    fn constant_environment_is_the_same_everywhere() {
+        let environment = Constant::new(Vec3::new(0.1, 0.2, 0.3));
+
+        assert_eq!(
+            Vec3::new(0.1, 0.2, 0.3),
+            environment.radiance(Vec3::new(0.0, 1.0, 0.0))
+        );
+        assert_eq!(
+            Vec3::new(0.1, 0.2, 0.3),
+            environment.radiance(Vec3::new(1.0, -5.0, 0.0))
+        );
+        assert!(environment.sample(&mut thread_rng()).is_none());
+    }
+
+    #[test]
+    fn gradient_blends_from_bottom_to_top() {
+        let environment = Gradient::default();
+
+        assert_eq!(
+            Vec3::new(1.0, 1.0, 1.0),
+            environment.radiance(Vec3::new(0.0, -2.0, 0.0))
+        );
+        assert_eq!(
+            Vec3::new(0.5, 0.7, 1.0),
+            environment.radiance(Vec3::new(0.0, 2.0, 0.0))
+        );
+        assert_eq!(
+            Vec3::new(0.75, 0.85, 1.0),
+            environment.radiance(Vec3::new(1.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn environment_map_looks_up_texels_by_direction() {
+        let pixels = (0..8).map(|index| Vec3::new(index as f64, 0.0, 0.0));
+        let environment = EnvironmentMap::new(4, 2, pixels.collect());
+
+        // straight ahead is the center of the image
+        assert_eq!(
+            Vec3::new(6.0, 0.0, 0.0),
+            environment.radiance(Vec3::new(0.01, -0.1, -1.0))
+        );
+        assert_eq!(
+            Vec3::new(1.0, 0.0, 0.0),
+            environment.radiance(Vec3::new(-0.01, 0.1, -1.0))
+        );
+        // straight behind wraps around the edges
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            environment.radiance(Vec3::new(-0.01, 0.1, 1.0))
+        );
+        assert_eq!(
+            Vec3::new(3.0, 0.0, 0.0),
+            environment.radiance(Vec3::new(0.01, 0.1, 1.0))
+        );
+    }
+
+    #[test]
+    fn environment_map_samples_the_bright_texels() {
+        let mut rng = thread_rng();
+        let mut pixels = vec![Vec3::new(0.0, 0.0, 0.0); 16 * 8];
+        // the "sun"
+        pixels[2 * 16 + 5] = Vec3::new(100.0, 100.0, 100.0);
+        let environment = EnvironmentMap::new(16, 8, pixels);
+
+        for _ in 0..100 {
+            let sample = environment.sample(&mut rng).expect("Should sample");
+
+            assert_eq!(Vec3::new(100.0, 100.0, 100.0), sample.radiance);
+            assert_eq!(sample.radiance, environment.radiance(sample.direction));
+            assert!((sample.pdf - environment.pdf(sample.direction)).abs() < 1e-10);
+            assert!((sample.direction.length() - 1.0).abs() < 1e-10);
+        }
+
+        assert_eq!(0.0, environment.pdf(Vec3::new(0.0, -1.0, 0.1)));
+    }
+
+    #[test]
+    fn black_environment_map_is_not_sampled() {
+        let environment = EnvironmentMap::new(2, 2, vec![Vec3::new(0.0, 0.0, 0.0); 4]);
+
+        assert!(environment.sample(&mut thread_rng()).is_none());
+        assert_eq!(0.0, environment.pdf(Vec3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn environment_map_pdf_integrates_to_one() {
+        let pixels = (0..32)
+            .map(|index| Vec3::new(1.0 + (index * 7 % 5) as f64, 0.5, index as f64))
+            .collect();
+        let environment = EnvironmentMap::new(8, 4, pixels);
+
+        // midpoint rule over the sphere, with the steps aligned to the texels
+        let (steps_phi, steps_theta) = (8 * 32, 4 * 32);
+        let (d_phi, d_theta) = (2.0 * PI / steps_phi as f64, PI / steps_theta as f64);
+        let mut integral = 0.0;
+        for i in 0..steps_theta {
+            let theta = (i as f64 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let direction = Vec3::new(
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    -theta.sin() * phi.cos(),
+                );
+
+                integral += environment.pdf(direction) * theta.sin() * d_theta * d_phi;
+            }
+        }
+
+        assert!((integral - 1.0).abs() < 1e-6, "{}", integral);
+    }
+
+    #[test]
+    #[should_panic(expected = "Empty environment map")]
+    fn environment_map_panics_when_empty() {
+        EnvironmentMap::new(0, 4, vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The pixels don't match the environment map size")]
+    fn environment_map_panics_on_the_wrong_number_of_pixels() {
+        EnvironmentMap::new(2, 2, vec![Vec3::new(0.0, 0.0, 0.0); 3]);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::image::Image;
+use crate::Vec3;
+
+/// The samples accumulated in a single pixel of a `Film`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FilmPixel {
+    /// The sum of the weighted radiance of the samples.
+    pub radiance: Vec3,
+    /// The sum of the weights of the samples.
+    pub weight: f64,
+    pub samples: u32,
+}
+
+impl Default for FilmPixel {
+    fn default() -> Self {
+        Self {
+            radiance: Vec3::new(0.0, 0.0, 0.0),
+            weight: 0.0,
+            samples: 0,
+        }
+    }
+}
+
+impl FilmPixel {
+    /// The weighted average of the samples, black without any.
+    pub fn resolve(&self) -> Vec3 {
+        if self.weight > 0.0 {
+            self.radiance / self.weight
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn add(&mut self, other: &FilmPixel) {
+        self.radiance += other.radiance;
+        self.weight += other.weight;
+        self.samples += other.samples;
+    }
+}
+
+/// Accumulates radiance samples into pixels, stored in scanline order starting from
+/// the top left corner.
+///
+/// Films of parts of the image can be rendered independently and merged into the
+/// full one, before resolving it into the final image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    /// Creates a film without any samples.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![FilmPixel::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> &FilmPixel {
+        assert!(x < self.width && y < self.height, "Pixel out of bounds");
+
+        &self.pixels[y * self.width + x]
+    }
+
+    /// Adds the `radiance` with the given `weight` to the pixel containing the film
+    /// position `x`, `y`, which goes from the top left corner at `(0, 0)` to the
+    /// bottom right one at `(width, height)`.
+    ///
+    /// Samples outside of the film are dropped.
+    pub fn add_sample(&mut self, x: f64, y: f64, radiance: Vec3, weight: f64) {
+        if !(x >= 0.0 && y >= 0.0) {
+            return;
+        }
+
+        let (column, row) = (x as usize, y as usize);
+        if column >= self.width || row >= self.height {
+            return;
+        }
+
+        self.pixels[row * self.width + column].add(&FilmPixel {
+            radiance: weight * radiance,
+            weight,
+            samples: 1,
+        });
+    }
+
+    /// Adds the samples of the `tile` film, with its top left corner at the pixel
+    /// `x`, `y` of this film. The parts of the tile outside of the film are
+    /// dropped.
+    pub fn merge(&mut self, tile: &Film, x: usize, y: usize) {
+        let width = tile.width.min(self.width.saturating_sub(x));
+        let height = tile.height.min(self.height.saturating_sub(y));
+
+        for row in 0..height {
+            let start = (y + row) * self.width + x;
+            let tile_row = &tile.pixels[row * tile.width..row * tile.width + width];
+
+            for (pixel, tile_pixel) in self.pixels[start..start + width].iter_mut().zip(tile_row) {
+                pixel.add(tile_pixel);
+            }
+        }
+    }
+
+    /// The image of the weighted average radiance of every pixel.
+    pub fn resolve(&self) -> Image {
+        Image::from_pixels(
+            self.width,
+            self.height,
+            self.pixels.iter().map(FilmPixel::resolve).collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_averages_the_weighted_samples() {
+        let mut film = Film::new(2, 2);
+
+        film.add_sample(1.25, 0.5, Vec3::new(1.0, 2.0, 3.0), 1.0);
+        film.add_sample(1.75, 0.99, Vec3::new(4.0, 5.0, 6.0), 3.0);
+        film.add_sample(0.5, 1.5, Vec3::new(1.0, 1.0, 1.0), 0.5);
+
+        assert_eq!(
+            &FilmPixel {
+                radiance: Vec3::new(13.0, 17.0, 21.0),
+                weight: 4.0,
+                samples: 2,
+            },
+            film.get(1, 0)
+        );
+        assert_eq!(
+            vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(3.25, 4.25, 5.25),
+                Vec3::new(1.0, 1.0, 1.0),
+                Vec3::new(0.0, 0.0, 0.0),
+            ],
+            film.resolve().pixels()
+        );
+    }
+
+    #[test]
+    fn it_drops_samples_outside_of_the_film() {
+        let mut film = Film::new(2, 2);
+        let radiance = Vec3::new(1.0, 1.0, 1.0);
+
+        film.add_sample(-0.5, 1.0, radiance, 1.0);
+        film.add_sample(1.0, 2.0, radiance, 1.0);
+        film.add_sample(2.5, 0.0, radiance, 1.0);
+        film.add_sample(f64::NAN, 0.0, radiance, 1.0);
+
+        assert_eq!(Film::new(2, 2), film);
+    }
+
+    #[test]
+    fn it_merges_tiles() {
+        let mut film = Film::new(3, 2);
+        film.add_sample(2.5, 1.5, Vec3::new(1.0, 0.0, 0.0), 1.0);
+        let mut tile = Film::new(2, 2);
+        tile.add_sample(0.5, 0.5, Vec3::new(0.0, 1.0, 0.0), 1.0);
+        tile.add_sample(1.5, 1.5, Vec3::new(0.0, 0.0, 1.0), 1.0);
+
+        film.merge(&tile, 1, 0);
+        // the part hanging over the right edge is dropped
+        film.merge(&tile, 2, 1);
+
+        assert_eq!(Vec3::new(0.0, 1.0, 0.0), film.get(1, 0).resolve());
+        assert_eq!(
+            &FilmPixel {
+                radiance: Vec3::new(1.0, 1.0, 1.0),
+                weight: 3.0,
+                samples: 3,
+            },
+            film.get(2, 1)
+        );
+        assert_eq!(0, film.get(0, 0).samples);
+    }
+}
@@ -1,62 +1,172 @@
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::material::Material;
 use crate::ray::Ray;
 use crate::Vec3;
 
-#[derive(Debug, PartialEq)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub t: f64,
     pub p: Vec3,
     pub normal: Vec3,
+    /// The material of the surface that was hit.
+    pub material: &'a dyn Material,
+    /// The weights of the three vertices at the hit point, for triangles only.
+    pub barycentric: Option<[f64; 3]>,
+    /// The surface coordinates of the hit point, used to look up textures.
+    pub u: f64,
+    pub v: f64,
+    /// False when a two-sided surface was hit from behind, in which case the
+    /// `normal` was flipped to face the ray.
+    pub front_face: bool,
+}
+
+impl<'a> HitRecord<'a> {
+    pub fn new(t: f64, p: Vec3, normal: Vec3, material: &'a dyn Material) -> Self {
+        Self {
+            t,
+            p,
+            normal,
+            material,
+            barycentric: None,
+            u: 0.0,
+            v: 0.0,
+            front_face: true,
+        }
+    }
+
+    pub fn with_barycentric(mut self, barycentric: [f64; 3]) -> Self {
+        self.barycentric = Some(barycentric);
+        self
+    }
+
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.u = u;
+        self.v = v;
+        self
+    }
+
+    /// Flips the normal of a surface hit from behind, so that it faces the ray.
+    pub fn with_front_face(mut self, front_face: bool) -> Self {
+        if !front_face {
+            self.normal = -self.normal;
+        }
+        self.front_face = front_face;
+        self
+    }
 }
 
-impl HitRecord {
-    pub fn new(t: f64, p: Vec3, normal: Vec3) -> Self {
-        Self { t, p, normal }
+pub trait Hitable: Send + Sync {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// The box containing every point the hitable can be hit at, `None` when it's
+    /// unbounded.
+    fn bounding_box(&self) -> Option<Aabb>;
+}
+
+impl<H: Hitable + ?Sized> Hitable for Box<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
     }
 }
 
-pub trait Hitable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+impl<H: Hitable + ?Sized> Hitable for Arc<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
 }
 
-pub struct HitableList<'a, H: Hitable> {
-    hitable: &'a [H],
-    index: usize,
+/// An owned scene of any mix of hitables, which returns the closest hit of all.
+#[derive(Default)]
+pub struct HitableList {
+    hitables: Vec<Box<dyn Hitable>>,
 }
 
-impl<'a, H: Hitable> HitableList<'a, H> {
-    pub fn new(hitable: &'a [H]) -> Self {
-        Self { hitable, index: 0 }
+impl HitableList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<H: Hitable + 'static>(&mut self, hitable: H) {
+        self.hitables.push(Box::new(hitable));
+    }
+
+    pub fn len(&self) -> usize {
+        self.hitables.len()
     }
 
-    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        self.hitable.iter().fold(None, |closest, hitable| {
+    pub fn is_empty(&self) -> bool {
+        self.hitables.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Hitable> {
+        self.hitables.iter().map(|hitable| &**hitable)
+    }
+}
+
+impl Hitable for HitableList {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.hitables.iter().fold(None, |closest, hitable| {
             let closest_so_far = closest.as_ref().map_or(t_max, |hr| hr.t);
 
             hitable.hit(ray, t_min, closest_so_far).or(closest)
         })
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.hitables
+            .iter()
+            .try_fold(Aabb::empty(), |aabb, hitable| {
+                Some(aabb.union(&hitable.bounding_box()?))
+            })
+    }
 }
 
-impl<'a, H: Hitable> Iterator for HitableList<'a, H> {
-    type Item = &'a H;
+impl IntoIterator for HitableList {
+    type Item = Box<dyn Hitable>;
+    type IntoIter = std::vec::IntoIter<Box<dyn Hitable>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.hitables.into_iter()
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let current = self.hitable.get(self.index);
-        self.index += 1;
+impl<H: Hitable + 'static> From<Vec<H>> for HitableList {
+    fn from(vec: Vec<H>) -> Self {
+        vec.into_iter().collect()
+    }
+}
 
-        current
+impl<H: Hitable + 'static> FromIterator<H> for HitableList {
+    fn from_iter<I: IntoIterator<Item = H>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
     }
 }
 
-impl<'a, H: Hitable> From<&'a Vec<H>> for HitableList<'a, H> {
-    fn from(vec: &'a Vec<H>) -> HitableList<'a, H> {
-        HitableList::new(vec)
+impl<H: Hitable + 'static> Extend<H> for HitableList {
+    fn extend<I: IntoIterator<Item = H>>(&mut self, iter: I) {
+        for hitable in iter {
+            self.push(hitable);
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::material::Lambertian;
+    use crate::object::{Quad, Sphere};
 
     #[derive(Debug, Eq, PartialEq)]
     struct HitDummy {}
@@ -66,29 +176,64 @@ mod test {
         }
     }
     impl Hitable for HitDummy {
-        fn hit(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord> {
+        fn hit(&self, _ray: &Ray, _t_min: f64, _t_max: f64) -> Option<HitRecord<'_>> {
+            None
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
             None
         }
     }
 
     #[test]
-    fn hitable_list_iterates_over_hitables() {
-        let dummy_one = HitDummy::new();
-        let dummy_two = HitDummy::new();
-        let slice = vec![dummy_one, dummy_two];
-        let mut hitable_list = HitableList::new(&slice);
+    fn hitable_list_from_a_vec() {
+        let vector = vec![HitDummy::new(), HitDummy::new()];
 
-        assert_eq!(Some(&HitDummy::new()), hitable_list.next());
-        assert_eq!(Some(&HitDummy::new()), hitable_list.next());
-        assert_eq!(None, hitable_list.next());
+        let hitable_list = HitableList::from(vector);
+
+        assert_eq!(2, hitable_list.len());
+        assert_eq!(2, hitable_list.iter().count());
     }
 
     #[test]
-    fn hitable_list_from_a_vec() {
-        let vector = vec![HitDummy::new(), HitDummy::new()];
+    fn hitable_list_mixes_hitables() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let mut hitable_list = HitableList::new();
+        assert!(hitable_list.is_empty());
+
+        hitable_list.push(HitDummy::new());
+        hitable_list.push(Sphere::new(
+            Vec3::new(0.0, 0.0, -3.0),
+            1.0,
+            material.clone(),
+        ));
+        let shared: Arc<dyn Hitable> = Arc::new(Quad::new(
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            material,
+        ));
+        hitable_list.push(shared.clone());
+        assert_eq!(3, hitable_list.len());
+
+        // the quad is in front of the sphere
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = hitable_list.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!(1.0, hit_record.t);
+
+        let hit_record = hitable_list.hit(&ray, 1.5, 10.0).expect("Should hit");
+        assert_eq!(2.0, hit_record.t);
+    }
+
+    #[test]
+    fn hitable_list_is_shared_across_threads() {
+        let hitable_list: Arc<HitableList> = Arc::new(vec![HitDummy::new()].into());
 
-        let hitable_list = HitableList::from(&vector);
+        let handle = {
+            let hitable_list = hitable_list.clone();
+            std::thread::spawn(move || hitable_list.len())
+        };
 
-        assert_eq!(2, hitable_list.count());
+        assert_eq!(1, handle.join().expect("Should join"));
     }
 }
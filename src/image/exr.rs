@@ -0,0 +1,283 @@
+//! Encoding of OpenEXR `.exr` images.
+//!
+//! Only single part scanline images with 32 bit float `R`, `G` and `B` channels
+//! are written, either uncompressed or ZIP compressed.
+
+use std::io::{Error, ErrorKind, Result, Write};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::Image;
+use crate::Vec3;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+const FLOAT: i32 = 2;
+const NO_COMPRESSION: u8 = 0;
+const ZIP_COMPRESSION: u8 = 3;
+/// The number of scanlines stored together in a ZIP compressed chunk.
+const ZIP_SCANLINES: usize = 16;
+
+/// Writes a scanline OpenEXR image, ZIP compressing the chunks if `zip` is set.
+pub(crate) fn encode<W: Write>(image: &Image, mut writer: W, zip: bool) -> Result<()> {
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 || width > i32::MAX as usize || height > i32::MAX as usize {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "OpenEXR images must not be empty or too large",
+        ));
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION);
+
+    let mut channels = vec![];
+    // the channels are sorted by their names
+    for name in &["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&FLOAT.to_le_bytes());
+        // linear flag and reserved bytes, then the x and y sampling
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1_i32.to_le_bytes());
+        channels.extend_from_slice(&1_i32.to_le_bytes());
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+
+    let compression = if zip { ZIP_COMPRESSION } else { NO_COMPRESSION };
+    attribute(&mut header, "compression", "compression", &[compression]);
+
+    let window = [0, 0, width as i32 - 1, height as i32 - 1];
+    let window: Vec<u8> = window
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    // increasing y
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1_f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1_f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    let scanlines = if zip { ZIP_SCANLINES } else { 1 };
+    let chunks: Vec<Vec<u8>> = image
+        .pixels()
+        .chunks(width * scanlines)
+        .enumerate()
+        .map(|(index, rows)| {
+            let data = scanline_data(rows, width);
+            let data = if zip { compress(&data)? } else { data };
+
+            let mut chunk = vec![];
+            chunk.extend_from_slice(&((index * scanlines) as i32).to_le_bytes());
+            chunk.extend_from_slice(&(data.len() as i32).to_le_bytes());
+            chunk.extend_from_slice(&data);
+            Ok(chunk)
+        })
+        .collect::<Result<_>>()?;
+
+    // the offset table points at the start of every chunk in the file
+    let mut offset = (header.len() + 8 * chunks.len()) as u64;
+    for chunk in &chunks {
+        header.extend_from_slice(&offset.to_le_bytes());
+        offset += chunk.len() as u64;
+    }
+
+    writer.write_all(&header)?;
+    for chunk in &chunks {
+        writer.write_all(chunk)?;
+    }
+
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Every scanline stores all of its blue samples, then the green and then the red
+/// ones.
+fn scanline_data(rows: &[Vec3], width: usize) -> Vec<u8> {
+    rows.chunks(width)
+        .flat_map(|row| {
+            [2, 1, 0].iter().flat_map(move |&channel| {
+                row.iter()
+                    .flat_map(move |pixel| (pixel[channel] as f32).to_le_bytes())
+            })
+        })
+        .collect()
+}
+
+/// Splits the bytes into even and odd halves and stores the differences between
+/// neighbours before deflating them, keeping the raw data if that is smaller.
+fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    let mut reordered: Vec<u8> = data
+        .iter()
+        .step_by(2)
+        .chain(data.iter().skip(1).step_by(2))
+        .copied()
+        .collect();
+
+    for index in (1..reordered.len()).rev() {
+        reordered[index] = reordered[index]
+            .wrapping_sub(reordered[index - 1])
+            .wrapping_add(128);
+    }
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(&reordered)?;
+    let compressed = encoder.finish()?;
+
+    Ok(if compressed.len() < data.len() {
+        compressed
+    } else {
+        data.to_vec()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Image {
+        let pixels = (0..width * height)
+            .map(|index| {
+                let value = (index % width) as f64 / width as f64;
+                Vec3::new(value, 2.0 * value, 100.0)
+            })
+            .collect();
+
+        Image::from_pixels(width, height, pixels)
+    }
+
+    fn read_i32(bytes: &[u8], position: usize) -> i32 {
+        i32::from_le_bytes([
+            bytes[position],
+            bytes[position + 1],
+            bytes[position + 2],
+            bytes[position + 3],
+        ])
+    }
+
+    fn read_u64(bytes: &[u8], position: usize) -> u64 {
+        let mut value = [0; 8];
+        value.copy_from_slice(&bytes[position..position + 8]);
+        u64::from_le_bytes(value)
+    }
+
+    /// The `(y, data)` of every chunk in the offset table.
+    fn chunks(bytes: &[u8], count: usize) -> Vec<(i32, Vec<u8>)> {
+        let string_end =
+            |start: usize| start + bytes[start..].iter().position(|&byte| byte == 0).unwrap();
+
+        // skip the attributes up to the empty name ending the header
+        let mut position = 8;
+        while bytes[position] != 0 {
+            let kind_end = string_end(string_end(position) + 1);
+            position = kind_end + 5 + read_i32(bytes, kind_end + 1) as usize;
+        }
+        let table = position + 1;
+
+        (0..count)
+            .map(|index| {
+                let offset = read_u64(bytes, table + 8 * index) as usize;
+                let size = read_i32(bytes, offset + 4) as usize;
+
+                (
+                    read_i32(bytes, offset),
+                    bytes[offset + 8..offset + 8 + size].to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_writes_uncompressed_scanlines() {
+        let image = gradient(4, 3);
+        let mut bytes = vec![];
+
+        encode(&image, &mut bytes, false).expect("Should encode");
+
+        assert_eq!(&MAGIC, &bytes[..4]);
+        let chunks = chunks(&bytes, 3);
+        assert_eq!(
+            vec![0, 1, 2],
+            chunks.iter().map(|(y, _)| *y).collect::<Vec<_>>()
+        );
+
+        let samples: Vec<f32> = chunks[1]
+            .1
+            .chunks_exact(4)
+            .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))
+            .collect();
+        assert_eq!(
+            vec![100.0, 100.0, 100.0, 100.0, 0.0, 0.5, 1.0, 1.5, 0.0, 0.25, 0.5, 0.75],
+            samples
+        );
+    }
+
+    #[test]
+    fn it_zip_compresses_blocks_of_scanlines() {
+        let image = gradient(64, 20);
+        let mut uncompressed = vec![];
+        let mut zipped = vec![];
+
+        encode(&image, &mut uncompressed, false).expect("Should encode");
+        encode(&image, &mut zipped, true).expect("Should encode");
+
+        assert!(zipped.len() < uncompressed.len());
+        let chunks = chunks(&zipped, 2);
+        assert_eq!(
+            vec![0, 16],
+            chunks.iter().map(|(y, _)| *y).collect::<Vec<_>>()
+        );
+
+        // undo the compression, the differences and the reordering
+        let mut data = vec![];
+        ZlibDecoder::new(&chunks[0].1[..])
+            .read_to_end(&mut data)
+            .expect("Should inflate");
+        for index in 1..data.len() {
+            data[index] = data[index].wrapping_add(data[index - 1]).wrapping_sub(128);
+        }
+        let (even, odd) = data.split_at(data.len().div_ceil(2));
+        let data: Vec<u8> = even
+            .iter()
+            .zip(odd)
+            .flat_map(|(even, odd)| [*even, *odd])
+            .collect();
+
+        assert_eq!(scanline_data(&image.pixels()[..64 * 16], 64), data);
+    }
+
+    #[test]
+    fn it_rejects_empty_images() {
+        let error = encode(&Image::new(0, 4), vec![], false).expect_err("Should be empty");
+
+        assert_eq!(ErrorKind::InvalidInput, error.kind());
+    }
+}
@@ -0,0 +1,369 @@
+//! Decoding and encoding of Radiance `.hdr` (RGBE) images.
+
+use std::io::{BufRead, Read, Result, Write};
+
+use super::{invalid_data, Image};
+use crate::Vec3;
+
+/// The largest number of pixels read, protecting against allocating huge buffers
+/// for corrupt headers.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// Reads a Radiance HDR image.
+pub(crate) fn decode<R: BufRead>(mut reader: R) -> Result<Image> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("Missing Radiance HDR signature"));
+    }
+
+    // the header ends with an empty line
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("Unexpected end of the Radiance HDR header"));
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid_data("Only the 32-bit_rle_rgbe format is supported"));
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let (width, height) = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["-Y", height, "+X", width] => (parse_dimension(width)?, parse_dimension(height)?),
+        _ => {
+            return Err(invalid_data(
+                "Only the standard `-Y height +X width` orientation is supported",
+            ))
+        }
+    };
+    let count = width
+        .checked_mul(height)
+        .filter(|&count| count > 0 && count <= MAX_PIXELS)
+        .ok_or_else(|| invalid_data("Invalid image dimension"))?;
+
+    let mut pixels = Vec::with_capacity(count);
+    let mut scanline = vec![[0_u8; 4]; width];
+    for _ in 0..height {
+        read_scanline(&mut reader, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|rgbe| rgbe_to_vec3(*rgbe)));
+    }
+
+    Ok(Image::from_pixels(width, height, pixels))
+}
+
+/// Writes a Radiance HDR image, run length encoding the scanlines when they are
+/// long enough for it.
+pub(crate) fn encode<W: Write>(image: &Image, mut writer: W) -> Result<()> {
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        image.height(),
+        image.width()
+    )?;
+
+    let width = image.width();
+    let mut bytes = vec![];
+    for row in image.pixels().chunks(width.max(1)) {
+        let scanline: Vec<[u8; 4]> = row.iter().map(|pixel| vec3_to_rgbe(*pixel)).collect();
+
+        if !(8..0x8000).contains(&width) {
+            bytes.extend(scanline.iter().flatten());
+            continue;
+        }
+
+        bytes.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+        for component in 0..4 {
+            let values: Vec<u8> = scanline.iter().map(|rgbe| rgbe[component]).collect();
+            write_runs(&values, &mut bytes);
+        }
+    }
+
+    writer.write_all(&bytes)
+}
+
+/// Run length encodes a single component of a scanline, repeating values become
+/// runs and everything else literal dumps.
+fn write_runs(values: &[u8], bytes: &mut Vec<u8>) {
+    const MIN_RUN: usize = 4;
+
+    let mut x = 0;
+    while x < values.len() {
+        // find the next run which is long enough to be worth it
+        let mut run_start = x;
+        let mut run = 0;
+        while run_start < values.len() {
+            run = values[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&value| value == values[run_start])
+                .count();
+            if run >= MIN_RUN {
+                break;
+            }
+            run_start += run;
+        }
+
+        // dump the values before the run
+        while x < run_start {
+            let count = (run_start - x).min(128);
+            bytes.push(count as u8);
+            bytes.extend_from_slice(&values[x..x + count]);
+            x += count;
+        }
+
+        if run >= MIN_RUN {
+            bytes.extend_from_slice(&[128 + run as u8, values[run_start]]);
+            x += run;
+        }
+    }
+}
+
+fn read_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> Result<()> {
+    let width = scanline.len();
+    let mut first = [0_u8; 4];
+    reader.read_exact(&mut first)?;
+
+    let is_run_length_encoded = (8..0x8000).contains(&width) && first[0] == 2 && first[1] == 2;
+    if !is_run_length_encoded {
+        scanline[0] = first;
+        return read_flat_scanline(reader, scanline);
+    }
+
+    if usize::from(first[2]) << 8 | usize::from(first[3]) != width {
+        return Err(invalid_data("Wrong scanline width"));
+    }
+
+    // every component is stored separately and run length encoded
+    for component in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0_u8; 2];
+            reader.read_exact(&mut count[..1])?;
+
+            if count[0] > 128 {
+                let run = usize::from(count[0] - 128);
+                reader.read_exact(&mut count[1..])?;
+                if x + run > width {
+                    return Err(invalid_data("Run exceeds the scanline"));
+                }
+                for pixel in &mut scanline[x..x + run] {
+                    pixel[component] = count[1];
+                }
+                x += run;
+            } else {
+                let run = usize::from(count[0]);
+                if run == 0 || x + run > width {
+                    return Err(invalid_data("Run exceeds the scanline"));
+                }
+                let mut values = vec![0_u8; run];
+                reader.read_exact(&mut values)?;
+                for (pixel, value) in scanline[x..x + run].iter_mut().zip(values) {
+                    pixel[component] = value;
+                }
+                x += run;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads uncompressed pixels, also handling the old style run length encoding
+/// where a `(1, 1, 1, count)` pixel repeats the previous one.
+fn read_flat_scanline<R: Read>(reader: &mut R, scanline: &mut [[u8; 4]]) -> Result<()> {
+    let mut x = 1;
+    let mut shift = 0;
+    while x < scanline.len() {
+        let mut rgbe = [0_u8; 4];
+        reader.read_exact(&mut rgbe)?;
+
+        if rgbe[0] == 1 && rgbe[1] == 1 && rgbe[2] == 1 {
+            let run = usize::from(rgbe[3]) << shift;
+            if x + run > scanline.len() {
+                return Err(invalid_data("Run exceeds the scanline"));
+            }
+            let previous = scanline[x - 1];
+            for pixel in &mut scanline[x..x + run] {
+                *pixel = previous;
+            }
+            x += run;
+            shift += 8;
+        } else {
+            scanline[x] = rgbe;
+            x += 1;
+            shift = 0;
+        }
+    }
+
+    Ok(())
+}
+
+fn rgbe_to_vec3(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+
+    let factor = 2.0_f64.powi(i32::from(rgbe[3]) - (128 + 8));
+
+    Vec3::new(
+        (f64::from(rgbe[0]) + 0.5) * factor,
+        (f64::from(rgbe[1]) + 0.5) * factor,
+        (f64::from(rgbe[2]) + 0.5) * factor,
+    )
+}
+
+/// Stores the color as a shared exponent and 8 bit mantissas, negative and NaN
+/// components become black and infinite ones saturate.
+fn vec3_to_rgbe(color: Vec3) -> [u8; 4] {
+    let component = |value: f64| {
+        if value > 0.0 {
+            value.min(f64::MAX)
+        } else {
+            0.0
+        }
+    };
+    let (r, g, b) = (
+        component(color.r()),
+        component(color.g()),
+        component(color.b()),
+    );
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // the mantissas are below 256 with an exponent of `max < 2^exponent`
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max >= 2.0_f64.powi(exponent) {
+        exponent += 1;
+    }
+    let exponent = exponent.clamp(-128, 127);
+    let factor = 2.0_f64.powi(8 - exponent);
+    let mantissa = |value: f64| (value * factor).min(255.0) as u8;
+
+    [
+        mantissa(r),
+        mantissa(g),
+        mantissa(b),
+        (exponent + 128) as u8,
+    ]
+}
+
+fn parse_dimension(value: &str) -> Result<usize> {
+    value
+        .parse()
+        .map_err(|_| invalid_data("Invalid image dimension"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(width: usize, height: usize) -> Vec<u8> {
+        format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            height, width
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn it_decodes_flat_pixels() {
+        let mut bytes = header(2, 1);
+        bytes.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+
+        let image = decode(&bytes[..]).expect("Should decode");
+
+        assert_eq!((2, 1), (image.width(), image.height()));
+        assert_eq!(
+            &[
+                Vec3::new(128.5 / 128.0, 64.5 / 128.0, 0.5 / 128.0),
+                Vec3::new(0.0, 0.0, 0.0)
+            ],
+            image.pixels()
+        );
+    }
+
+    #[test]
+    fn it_decodes_run_length_encoded_scanlines() {
+        let mut bytes = header(8, 1);
+        bytes.extend_from_slice(&[2, 2, 0, 8]);
+        // red: a run of 8
+        bytes.extend_from_slice(&[128 + 8, 127]);
+        // green: 8 literal values
+        bytes.extend_from_slice(&[8, 0, 1, 2, 3, 4, 5, 6, 7]);
+        // blue: two runs of 4
+        bytes.extend_from_slice(&[128 + 4, 0, 128 + 4, 255]);
+        // exponent
+        bytes.extend_from_slice(&[128 + 8, 128]);
+
+        let image = decode(&bytes[..]).expect("Should decode");
+
+        assert_eq!((8, 1), (image.width(), image.height()));
+        assert_eq!(Vec3::new(127.5, 0.5, 0.5) / 256.0, image.get(0, 0));
+        assert_eq!(Vec3::new(127.5, 7.5, 255.5) / 256.0, image.get(7, 0));
+    }
+
+    #[test]
+    fn it_encodes_what_it_decodes() {
+        for width in [3, 40] {
+            let pixels = (0..width * 2)
+                .map(|index| {
+                    // long runs of the same color alternating with noisy pixels
+                    let value = if index % 20 < 10 { 1.5 } else { index as f64 };
+                    Vec3::new(value, 0.001 * value, 1000.0)
+                })
+                .collect();
+            let image = Image::from_pixels(width, 2, pixels);
+
+            let mut bytes = vec![];
+            encode(&image, &mut bytes).expect("Should encode");
+            let decoded = decode(&bytes[..]).expect("Should decode");
+
+            assert_eq!((width, 2), (decoded.width(), decoded.height()));
+            for (expected, actual) in image.pixels().iter().zip(decoded.pixels()) {
+                // the smaller components lose precision to the shared exponent
+                assert!((*expected - *actual).length() / expected.length() < 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn it_stores_colors_with_a_shared_exponent() {
+        assert_eq!([128, 64, 0, 129], vec3_to_rgbe(Vec3::new(1.0, 0.5, 0.0)));
+        assert_eq!([0, 0, 0, 0], vec3_to_rgbe(Vec3::new(-1.0, f64::NAN, 0.0)));
+        assert_eq!(
+            [255, 0, 0, 129],
+            vec3_to_rgbe(Vec3::new(1.999_999_999, 0.0, 0.0))
+        );
+        assert_eq!(
+            [255, 0, 0, 255],
+            vec3_to_rgbe(Vec3::new(f64::INFINITY, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_files() {
+        assert!(decode(&b"P3\n1 1\n255\n"[..]).is_err());
+
+        let mut bytes = header(1, 1);
+        bytes.extend_from_slice(&[0, 0]);
+        assert!(decode(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn it_rejects_empty_and_huge_dimensions() {
+        for (width, height) in [(0, 1), (1, 0), (usize::MAX, 2), (1 << 20, 1 << 20)] {
+            let error = decode(&header(width, height)[..]).expect_err("Should be invalid");
+
+            assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+        }
+    }
+}
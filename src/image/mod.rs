@@ -0,0 +1,466 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+use crate::Vec3;
+
+mod exr;
+mod hdr;
+mod pfm;
+mod png;
+mod ppm;
+
+const PNG_SIGNATURE: &[u8] = &[137, 80, 78, 71, 13, 10, 26, 10];
+
+/// How coordinates outside of an image are mapped back onto it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    /// The image tiles the plane.
+    Repeat,
+    /// The edge pixels stretch out indefinitely.
+    Clamp,
+}
+
+/// The file formats images can be saved in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Binary (`P6`) PPM with 8 bits per sample.
+    Ppm,
+    /// PNG with 8 bits per sample.
+    Png,
+    /// PNG with 16 bits per sample.
+    Png16,
+    /// Portable Float Map with 32 bit float samples.
+    Pfm,
+    /// Radiance RGBE, 8 bit samples sharing an exponent.
+    Hdr,
+    /// Uncompressed OpenEXR with 32 bit float samples.
+    Exr,
+    /// ZIP compressed OpenEXR with 32 bit float samples.
+    ExrZip,
+}
+
+impl Format {
+    /// Picks the format by the extension of the `path`, `.png` files get 8 bits per
+    /// sample and `.exr` files are ZIP compressed. The other variants can be saved
+    /// with `Image::save_as`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "ppm" => Some(Format::Ppm),
+            "png" => Some(Format::Png),
+            "pfm" => Some(Format::Pfm),
+            "hdr" => Some(Format::Hdr),
+            "exr" => Some(Format::ExrZip),
+            _ => None,
+        }
+    }
+}
+
+/// An in-memory image of linear RGB colors, stored in scanline order starting
+/// from the top left corner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Image {
+    /// Creates a black image.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::from_pixels(
+            width,
+            height,
+            vec![Vec3::new(0.0, 0.0, 0.0); width * height],
+        )
+    }
+
+    /// # Panics
+    ///
+    /// If the number of `pixels` doesn't match the `width` and `height`.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        assert_eq!(
+            width * height,
+            pixels.len(),
+            "The pixels don't match the image size"
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Loads a PPM, PNG or Radiance HDR image, see `decode`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::decode(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a PPM (`P3` or `P6`), PNG or Radiance HDR image, telling the format
+    /// apart by the first bytes.
+    ///
+    /// PPM and PNG samples are taken to be sRGB encoded and converted to linear
+    /// colors, any alpha channel is dropped.
+    pub fn decode<R: BufRead>(mut reader: R) -> Result<Self> {
+        let signature = reader.fill_buf()?;
+
+        if signature.starts_with(b"#?") {
+            hdr::decode(reader)
+        } else if signature.starts_with(b"P3") || signature.starts_with(b"P6") {
+            ppm::decode(reader)
+        } else if signature.starts_with(PNG_SIGNATURE) {
+            png::decode(reader)
+        } else {
+            Err(invalid_data("Unsupported image format"))
+        }
+    }
+
+    /// Saves the image in the format picked by the extension of the `path`, see
+    /// `Format::from_path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let format = Format::from_path(&path).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "Unsupported image file extension")
+        })?;
+
+        self.save_as(path, format)
+    }
+
+    /// Saves the image in the `format` regardless of the extension of the `path`,
+    /// e.g. as a 16 bit PNG with `Format::Png16`.
+    pub fn save_as<P: AsRef<Path>>(&self, path: P, format: Format) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.encode(&mut writer, format)?;
+
+        writer.flush()
+    }
+
+    /// Writes the image in the `format`. The high dynamic range formats (PFM, HDR
+    /// and EXR) keep the linear colors, the others clamp them to `[0, 1]` and sRGB
+    /// encode them.
+    pub fn encode<W: Write>(&self, writer: W, format: Format) -> Result<()> {
+        match format {
+            Format::Ppm => ppm::encode(self, writer),
+            Format::Png => png::encode(self, writer, 8),
+            Format::Png16 => png::encode(self, writer, 16),
+            Format::Pfm => pfm::encode(self, writer),
+            Format::Hdr => hdr::encode(self, writer),
+            Format::Exr => exr::encode(self, writer, false),
+            Format::ExrZip => exr::encode(self, writer, true),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Vec3) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    /// Bilinearly filters the four pixels around the texture coordinates `u` and
+    /// `v`, which go from the top left corner at `(0, 0)` to the bottom right one at
+    /// `(1, 1)`. An empty image is black.
+    pub fn sample(&self, u: f64, v: f64, wrap: WrapMode) -> Vec3 {
+        if self.width == 0 || self.height == 0 {
+            return Vec3::new(0.0, 0.0, 0.0);
+        }
+
+        // the centers of the pixels are at the half coordinates
+        let x = u * self.width as f64 - 0.5;
+        let y = v * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let texel = |dx: i64, dy: i64| {
+            self.get(
+                wrapped(x0 as i64 + dx, self.width, wrap),
+                wrapped(y0 as i64 + dy, self.height, wrap),
+            )
+        };
+
+        (1.0 - ty) * ((1.0 - tx) * texel(0, 0) + tx * texel(1, 0))
+            + ty * ((1.0 - tx) * texel(0, 1) + tx * texel(1, 1))
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(x < self.width && y < self.height, "Pixel out of bounds");
+
+        y * self.width + x
+    }
+}
+
+fn wrapped(coordinate: i64, size: usize, wrap: WrapMode) -> usize {
+    let size = size as i64;
+
+    let coordinate = match wrap {
+        WrapMode::Repeat => coordinate.rem_euclid(size),
+        WrapMode::Clamp => coordinate.clamp(0, size - 1),
+    };
+
+    coordinate as usize
+}
+
+/// Undoes the sRGB transfer function of a sample between `0` and `1`.
+fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Applies the sRGB transfer function to a linear `value` and quantizes it to
+/// `bits` bits, anything outside of `[0, 1]` (and NaN) is clamped.
+fn linear_to_srgb(value: f64, bits: u32) -> u16 {
+    // NaN ends up as black
+    let value = if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 1.0)
+    };
+    let encoded = if value <= 0.003_130_8 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (encoded * f64::from((1_u32 << bits) - 1)).round() as u16
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn it_creates_a_black_image() {
+        let image = Image::new(3, 2);
+
+        assert_eq!(3, image.width());
+        assert_eq!(2, image.height());
+        assert_eq!(6, image.pixels().len());
+        assert!(image
+            .pixels()
+            .iter()
+            .all(|pixel| *pixel == Vec3::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn it_sets_and_gets_pixels_in_scanline_order() {
+        let mut image = Image::new(3, 2);
+
+        image.set(2, 0, Vec3::new(1.0, 2.0, 3.0));
+        image.set(0, 1, Vec3::new(4.0, 5.0, 6.0));
+
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0), image.get(2, 0));
+        assert_eq!(Vec3::new(4.0, 5.0, 6.0), image.get(0, 1));
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0), image.pixels()[2]);
+        assert_eq!(Vec3::new(4.0, 5.0, 6.0), image.pixels()[3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pixel out of bounds")]
+    fn it_panics_on_pixels_out_of_bounds() {
+        Image::new(3, 2).get(3, 0);
+    }
+
+    #[test]
+    fn it_samples_bilinearly() {
+        let image = Image::from_pixels(
+            2,
+            1,
+            vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)],
+        );
+
+        // the pixel centers
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            image.sample(0.25, 0.5, WrapMode::Clamp)
+        );
+        assert_eq!(
+            Vec3::new(1.0, 1.0, 1.0),
+            image.sample(0.75, 0.5, WrapMode::Clamp)
+        );
+        // halfway between them
+        assert_eq!(
+            Vec3::new(0.5, 0.5, 0.5),
+            image.sample(0.5, 0.5, WrapMode::Clamp)
+        );
+        assert_eq!(
+            Vec3::new(0.5, 0.5, 0.5),
+            image.sample(0.5, 0.9, WrapMode::Repeat)
+        );
+    }
+
+    #[test]
+    fn it_wraps_or_clamps_the_coordinates() {
+        let image = Image::from_pixels(
+            2,
+            1,
+            vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0)],
+        );
+
+        // at the left edge the image either continues from its right side or not
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            image.sample(0.0, 0.5, WrapMode::Clamp)
+        );
+        assert_eq!(
+            Vec3::new(0.5, 0.5, 0.5),
+            image.sample(0.0, 0.5, WrapMode::Repeat)
+        );
+
+        assert_eq!(
+            Vec3::new(1.0, 1.0, 1.0),
+            image.sample(2.75, -3.0, WrapMode::Repeat)
+        );
+        assert_eq!(
+            Vec3::new(1.0, 1.0, 1.0),
+            image.sample(5.0, 0.5, WrapMode::Clamp)
+        );
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            image.sample(-5.0, 0.5, WrapMode::Clamp)
+        );
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            Image::new(0, 0).sample(0.5, 0.5, WrapMode::Repeat)
+        );
+    }
+
+    #[test]
+    fn it_opens_images_by_their_contents() {
+        let directory = TempDir::new("it_opens_images_by_their_contents");
+        let path = directory.join("image.ppm");
+        std::fs::write(&path, "P3 1 1 255 255 255 255").expect("Should write the image");
+
+        let image = Image::open(&path).expect("Should open the image");
+
+        assert_eq!(vec![Vec3::new(1.0, 1.0, 1.0)], image.pixels());
+        std::fs::remove_file(&path).expect("Should remove the image");
+        assert!(Image::open(&path).is_err());
+
+        let error = Image::decode(&b"GIF89a"[..]).expect_err("Should be unsupported");
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+
+    fn gradient() -> Image {
+        let pixels = (0..64)
+            .map(|index| {
+                let value = f64::from(index) / 63.0;
+                Vec3::new(value, 1.0 - value, value.powi(2))
+            })
+            .collect();
+
+        Image::from_pixels(8, 8, pixels)
+    }
+
+    #[test]
+    fn it_encodes_and_decodes_the_same_image() {
+        let image = gradient();
+
+        for (format, tolerance) in [
+            (Format::Ppm, 1e-2),
+            (Format::Png, 1e-2),
+            (Format::Png16, 1e-4),
+        ] {
+            let mut bytes = vec![];
+            image.encode(&mut bytes, format).expect("Should encode");
+            let decoded = Image::decode(&bytes[..]).expect("Should decode");
+
+            assert_eq!((8, 8), (decoded.width(), decoded.height()));
+            for (expected, actual) in image.pixels().iter().zip(decoded.pixels()) {
+                assert!((*expected - *actual).length() < tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn it_clamps_the_colors_it_encodes() {
+        let image = Image::from_pixels(
+            3,
+            1,
+            vec![
+                Vec3::new(2.0, -1.0, f64::NAN),
+                Vec3::new(0.5, 0.5, 0.5),
+                Vec3::new(1.0, 0.0, 0.0),
+            ],
+        );
+        let mut bytes = vec![];
+
+        image
+            .encode(&mut bytes, Format::Ppm)
+            .expect("Should encode");
+
+        assert_eq!(b"P6\n3 1\n255\n", &bytes[..11]);
+        assert_eq!(&[255, 0, 0, 188, 188, 188, 255, 0, 0], &bytes[11..]);
+    }
+
+    #[test]
+    fn it_saves_in_the_format_of_the_extension() {
+        assert_eq!(Some(Format::Ppm), Format::from_path("image.ppm"));
+        assert_eq!(Some(Format::Png), Format::from_path("renders/IMAGE.PNG"));
+        assert_eq!(Some(Format::Pfm), Format::from_path("image.pfm"));
+        assert_eq!(Some(Format::Hdr), Format::from_path("image.hdr"));
+        assert_eq!(Some(Format::ExrZip), Format::from_path("image.exr"));
+        assert_eq!(None, Format::from_path("image.gif"));
+        assert_eq!(None, Format::from_path("image"));
+
+        let directory = TempDir::new("it_saves_in_the_format_of_the_extension");
+        let image = gradient();
+
+        for name in &["image.ppm", "image.png", "image.hdr"] {
+            let path = directory.join(name);
+            image.save(&path).expect("Should save the image");
+
+            let loaded = Image::open(&path).expect("Should open the image");
+            assert_eq!((8, 8), (loaded.width(), loaded.height()));
+            std::fs::remove_file(&path).expect("Should remove the image");
+        }
+
+        let error = image
+            .save(directory.join("image.gif"))
+            .expect_err("Should be unsupported");
+        assert_eq!(ErrorKind::InvalidInput, error.kind());
+    }
+
+    #[test]
+    fn it_saves_sixteen_bit_pngs_in_the_given_format() {
+        let directory = TempDir::new("it_saves_sixteen_bit_pngs_in_the_given_format");
+        let path = directory.join("image.png");
+        let image = gradient();
+
+        image
+            .save_as(&path, Format::Png16)
+            .expect("Should save the image");
+
+        let bytes = std::fs::read(&path).expect("Should read the image");
+        // the bit depth in the header chunk
+        assert_eq!(16, bytes[24]);
+        let loaded = Image::open(&path).expect("Should open the image");
+        assert_eq!((8, 8), (loaded.width(), loaded.height()));
+        for (expected, actual) in image.pixels().iter().zip(loaded.pixels()) {
+            assert!((*expected - *actual).length() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Encoding of Portable Float Map `.pfm` images.
+
+use std::io::{Result, Write};
+
+use super::Image;
+
+/// Writes a little endian RGB PFM image of 32 bit floats, which stores the rows
+/// from the bottom to the top.
+pub(crate) fn encode<W: Write>(image: &Image, mut writer: W) -> Result<()> {
+    // the negative scale marks the samples as little endian
+    write!(writer, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+
+    let width = image.width().max(1);
+    let samples: Vec<u8> = image
+        .pixels()
+        .chunks(width)
+        .rev()
+        .flatten()
+        .flat_map(|pixel| [pixel.r(), pixel.g(), pixel.b()])
+        .flat_map(|sample| (sample as f32).to_le_bytes())
+        .collect();
+
+    writer.write_all(&samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vec3;
+
+    #[test]
+    fn it_writes_the_rows_bottom_up() {
+        let image = Image::from_pixels(
+            1,
+            2,
+            vec![Vec3::new(1.0, 2.0, 3.0), Vec3::new(-0.5, 1e6, 0.25)],
+        );
+        let mut bytes = vec![];
+
+        encode(&image, &mut bytes).expect("Should encode");
+
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&header[..], &bytes[..header.len()]);
+        let samples: Vec<f32> = bytes[header.len()..]
+            .chunks_exact(4)
+            .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))
+            .collect();
+        assert_eq!(vec![-0.5, 1e6, 0.25, 1.0, 2.0, 3.0], samples);
+    }
+}
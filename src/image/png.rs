@@ -0,0 +1,178 @@
+//! Decoding and encoding of `.png` images.
+
+use std::convert::TryFrom;
+use std::io::{Read, Result, Write};
+
+use ::png::{BitDepth, ColorType, Decoder, Encoder, SrgbRenderingIntent, Transformations};
+
+use super::{invalid_data, linear_to_srgb, srgb_to_linear, Image};
+use crate::Vec3;
+
+/// Reads a PNG image of any color type and bit depth, treating its samples as
+/// sRGB encoded and ignoring the alpha channel.
+pub(crate) fn decode<R: Read>(reader: R) -> Result<Image> {
+    let mut decoder = Decoder::new(reader);
+    // palettes and bit depths below 8 are expanded to 8 bit samples
+    decoder.set_transformations(Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    let buffer = &buffer[..info.buffer_size()];
+
+    let channels = match info.color_type {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        ColorType::Indexed => return Err(invalid_data("Unexpanded PNG palette")),
+    };
+    let samples: Vec<f64> = match info.bit_depth {
+        BitDepth::Eight => buffer
+            .iter()
+            .map(|&sample| f64::from(sample) / 255.0)
+            .collect(),
+        BitDepth::Sixteen => buffer
+            .chunks_exact(2)
+            .map(|sample| f64::from(u16::from_be_bytes([sample[0], sample[1]])) / 65535.0)
+            .collect(),
+        _ => return Err(invalid_data("Unexpanded PNG bit depth")),
+    };
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let pixels = samples
+        .chunks_exact(channels)
+        .take(width * height)
+        .map(|pixel| {
+            let color = if channels < 3 {
+                Vec3::new(pixel[0], pixel[0], pixel[0])
+            } else {
+                Vec3::new(pixel[0], pixel[1], pixel[2])
+            };
+
+            Vec3::new(
+                srgb_to_linear(color.r()),
+                srgb_to_linear(color.g()),
+                srgb_to_linear(color.b()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if pixels.len() != width * height {
+        return Err(invalid_data("Unexpected end of the PNG image"));
+    }
+
+    Ok(Image::from_pixels(width, height, pixels))
+}
+
+/// Writes an RGB PNG image with 8 or 16 `bits` per sRGB encoded sample.
+pub(crate) fn encode<W: Write>(image: &Image, writer: W, bits: u32) -> Result<()> {
+    let dimension = |size: usize| {
+        u32::try_from(size).map_err(|_| invalid_data("The image is too large for a PNG"))
+    };
+    let mut encoder = Encoder::new(
+        writer,
+        dimension(image.width())?,
+        dimension(image.height())?,
+    );
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(if bits == 16 {
+        BitDepth::Sixteen
+    } else {
+        BitDepth::Eight
+    });
+    encoder.set_source_srgb(SrgbRenderingIntent::Perceptual);
+
+    let samples = image
+        .pixels()
+        .iter()
+        .flat_map(|pixel| [pixel.r(), pixel.g(), pixel.b()]);
+    let data: Vec<u8> = if bits == 16 {
+        samples
+            .flat_map(|sample| linear_to_srgb(sample, 16).to_be_bytes())
+            .collect()
+    } else {
+        samples
+            .map(|sample| linear_to_srgb(sample, 8) as u8)
+            .collect()
+    };
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+
+    Ok(writer.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: BitDepth,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder = Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        encoder
+            .write_header()
+            .expect("Should write the header")
+            .write_image_data(data)
+            .expect("Should write the image");
+
+        bytes
+    }
+
+    #[test]
+    fn it_decodes_rgb_images() {
+        let bytes = encode(
+            2,
+            1,
+            ColorType::Rgba,
+            BitDepth::Eight,
+            &[255, 0, 0, 255, 0, 255, 255, 0],
+        );
+
+        let image = decode(&bytes[..]).expect("Should decode");
+
+        assert_eq!((2, 1), (image.width(), image.height()));
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), image.get(0, 0));
+        assert_eq!(Vec3::new(0.0, 1.0, 1.0), image.get(1, 0));
+    }
+
+    #[test]
+    fn it_decodes_grayscale_and_sixteen_bit_images() {
+        let bytes = encode(
+            1,
+            2,
+            ColorType::Grayscale,
+            BitDepth::Sixteen,
+            &[255, 255, 0, 0],
+        );
+
+        let image = decode(&bytes[..]).expect("Should decode");
+
+        assert_eq!(Vec3::new(1.0, 1.0, 1.0), image.get(0, 0));
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0), image.get(0, 1));
+
+        // 1 bit samples are expanded
+        let bytes = encode(2, 1, ColorType::Grayscale, BitDepth::One, &[0b0100_0000]);
+
+        let image = decode(&bytes[..]).expect("Should decode");
+
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0), image.get(0, 0));
+        assert_eq!(Vec3::new(1.0, 1.0, 1.0), image.get(1, 0));
+    }
+
+    #[test]
+    fn it_rejects_invalid_files() {
+        let bytes = encode(1, 1, ColorType::Rgb, BitDepth::Eight, &[0, 0, 0]);
+
+        assert!(decode(&bytes[..bytes.len() / 2]).is_err());
+        assert!(decode(&b"not a png"[..]).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! Netpbm `.ppm` images, decoding both the plain (`P3`) and the raw (`P6`)
+//! variant and encoding the raw one.
+
+use std::io::{BufRead, Result, Write};
+
+use super::{invalid_data, linear_to_srgb, srgb_to_linear, Image};
+use crate::Vec3;
+
+/// Reads a PPM image, treating its samples as sRGB encoded.
+pub(crate) fn decode<R: BufRead>(mut reader: R) -> Result<Image> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let mut header = Header {
+        bytes: &bytes,
+        position: 0,
+    };
+
+    let raw = match header.token()? {
+        "P3" => false,
+        "P6" => true,
+        _ => return Err(invalid_data("Only P3 and P6 PPM images are supported")),
+    };
+    let width = header.number()?;
+    let height = header.number()?;
+    let max_value = header.number()?;
+    if max_value == 0 || max_value > usize::from(u16::MAX) {
+        return Err(invalid_data("Invalid PPM maximum value"));
+    }
+
+    let count = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| invalid_data("Invalid image dimension"))?;
+    let samples = if raw {
+        // a single whitespace separates the header from the samples
+        let start = header.position + 1;
+        let size = if max_value < 256 { 1 } else { 2 };
+        let length = count
+            .checked_mul(size)
+            .ok_or_else(|| invalid_data("Invalid image dimension"))?;
+        let data = bytes
+            .get(start..)
+            .filter(|data| data.len() >= length)
+            .ok_or_else(|| invalid_data("Unexpected end of the PPM samples"))?;
+
+        data.chunks_exact(size)
+            .take(count)
+            .map(|sample| {
+                sample
+                    .iter()
+                    .fold(0, |value, &byte| value << 8 | usize::from(byte))
+            })
+            .collect::<Vec<_>>()
+    } else {
+        (0..count)
+            .map(|_| header.number())
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    if samples.iter().any(|&sample| sample > max_value) {
+        return Err(invalid_data("PPM sample exceeds the maximum value"));
+    }
+
+    let max_value = max_value as f64;
+    let pixels = samples
+        .chunks_exact(3)
+        .map(|rgb| {
+            let channel = |index: usize| srgb_to_linear(rgb[index] as f64 / max_value);
+            Vec3::new(channel(0), channel(1), channel(2))
+        })
+        .collect();
+
+    Ok(Image::from_pixels(width, height, pixels))
+}
+
+/// Writes a raw PPM image with 8 bits per sRGB encoded sample.
+pub(crate) fn encode<W: Write>(image: &Image, mut writer: W) -> Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", image.width(), image.height())?;
+
+    let samples: Vec<u8> = image
+        .pixels()
+        .iter()
+        .flat_map(|pixel| [pixel.r(), pixel.g(), pixel.b()])
+        .map(|sample| linear_to_srgb(sample, 8) as u8)
+        .collect();
+
+    writer.write_all(&samples)
+}
+
+/// Whitespace separated tokens which may be interleaved with `#` comments.
+struct Header<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Header<'a> {
+    fn token(&mut self) -> Result<&'a str> {
+        loop {
+            match self.bytes.get(self.position) {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                Some(b'#') => {
+                    while self
+                        .bytes
+                        .get(self.position)
+                        .is_some_and(|&byte| byte != b'\n')
+                    {
+                        self.position += 1;
+                    }
+                }
+                Some(_) => break,
+                None => return Err(invalid_data("Unexpected end of the PPM image")),
+            }
+        }
+
+        let start = self.position;
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|byte| !byte.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .map_err(|_| invalid_data("Invalid PPM header"))
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        self.token()?
+            .parse()
+            .map_err(|_| invalid_data("Invalid PPM number"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_decodes_plain_images() {
+        let image =
+            decode(&b"P3\n# a comment\n2 1\n255\n255 0 0\n0 0 255\n"[..]).expect("Should decode");
+
+        assert_eq!((2, 1), (image.width(), image.height()));
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), image.get(0, 0));
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), image.get(1, 0));
+    }
+
+    #[test]
+    fn it_decodes_raw_images() {
+        let mut bytes = b"P6 1 2 255\n".to_vec();
+        bytes.extend_from_slice(&[255, 255, 255, 0, 255, 0]);
+
+        let image = decode(&bytes[..]).expect("Should decode");
+
+        assert_eq!((1, 2), (image.width(), image.height()));
+        assert_eq!(Vec3::new(1.0, 1.0, 1.0), image.get(0, 0));
+        assert_eq!(Vec3::new(0.0, 1.0, 0.0), image.get(0, 1));
+
+        // two bytes per sample, most significant first
+        let mut bytes = b"P6\n1 1\n65535\n".to_vec();
+        bytes.extend_from_slice(&[255, 255, 0, 0, 255, 255]);
+
+        let image = decode(&bytes[..]).expect("Should decode");
+
+        assert_eq!(Vec3::new(1.0, 0.0, 1.0), image.get(0, 0));
+    }
+
+    #[test]
+    fn it_decodes_srgb_samples_to_linear_colors() {
+        let image = decode(&b"P3 1 1 255 128 128 128"[..]).expect("Should decode");
+
+        assert!((image.get(0, 0).x() - 0.2158605).abs() < 1e-6);
+    }
+
+    #[test]
+    fn it_rejects_invalid_files() {
+        assert!(decode(&b"P5 1 1 255 0"[..]).is_err());
+        assert!(decode(&b"P3 1 1 255 0 0"[..]).is_err());
+        assert!(decode(&b"P3 1 1 255 0 0 256"[..]).is_err());
+        assert!(decode(&b"P6 2 1 255\n\x00\x00\x00"[..]).is_err());
+        assert!(decode(&b"P3 x 1 255 0 0 0"[..]).is_err());
+    }
+
+    #[test]
+    fn it_rejects_sixteen_bit_images_too_large_to_address() {
+        let header = format!("P6 {} 3 65535\n\x00", usize::MAX / 9);
+
+        let error = decode(header.as_bytes()).expect_err("Should be invalid");
+
+        assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+    }
+}
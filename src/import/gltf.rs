@@ -0,0 +1,440 @@
+//! glTF 2.0 scenes, both `.gltf` and binary `.glb`.
+//!
+//! The nodes of the default scene are flattened with their transforms baked into
+//! the vertices, every primitive becoming its own mesh. The metallic-roughness
+//! materials are mapped onto the closest material of the crate:
+//!
+//! * emissive materials become a `DiffuseLight` with the emissive factor
+//! * blended materials which aren't fully opaque become a glass `Dielectric`
+//! * metallic materials become a `Metal` with the base color, as fuzzy as they
+//!   are rough
+//! * everything else becomes a `Lambertian` with the base color
+//!
+//! Textures are not supported yet, only the constant factors are used.
+
+use std::fs;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use ::gltf::camera::Projection;
+use ::gltf::material::AlphaMode;
+use ::gltf::mesh::Mode;
+use ::gltf::{buffer, Gltf, Node};
+
+use super::{default_material, invalid_data, Group};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::TriangleMesh;
+use crate::{Camera, Mat4, Vec3};
+
+/// The aspect ratio of cameras which leave it up to the viewport, the one of the
+/// default render `Settings`.
+const DEFAULT_ASPECT: f64 = 2.0;
+
+/// The meshes and cameras of a glTF scene, in world space.
+pub struct Scene {
+    pub groups: Vec<Group>,
+    pub cameras: Vec<Camera>,
+}
+
+/// Loads the `.gltf` or `.glb` file, along with any external buffers, which are
+/// looked up relative to it.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene> {
+    let path = path.as_ref();
+
+    import(&fs::read(path)?, path.parent())
+}
+
+/// Parses a `.glb` or a `.gltf` with all of its buffers embedded as data URIs.
+pub fn parse<R: Read>(mut reader: R) -> Result<Scene> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+
+    import(&bytes, None)
+}
+
+fn import(bytes: &[u8], base: Option<&Path>) -> Result<Scene> {
+    let Gltf { document, blob } = Gltf::from_slice(bytes).map_err(into_io_error)?;
+    let buffers = ::gltf::import_buffers(&document, base, blob).map_err(into_io_error)?;
+
+    let mut importer = Importer {
+        materials: document.materials().map(|m| map_material(&m)).collect(),
+        default_material: default_material(),
+        buffers,
+        scene: Scene {
+            groups: vec![],
+            cameras: vec![],
+        },
+    };
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next());
+    for node in scene.iter().flat_map(|scene| scene.nodes()) {
+        importer.visit(&node, &Mat4::identity())?;
+    }
+
+    Ok(importer.scene)
+}
+
+fn into_io_error(error: ::gltf::Error) -> Error {
+    match error {
+        ::gltf::Error::Io(error) => error,
+        error => Error::new(ErrorKind::InvalidData, error.to_string()),
+    }
+}
+
+fn map_material(material: &::gltf::Material<'_>) -> Arc<dyn Material> {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, alpha] = pbr.base_color_factor();
+    let base_color = Vec3::new(r.into(), g.into(), b.into());
+    let [r, g, b] = material.emissive_factor();
+    let emissive = Vec3::new(r.into(), g.into(), b.into());
+
+    if emissive.squared_length() > 0.0 {
+        Arc::new(DiffuseLight::new(emissive))
+    } else if material.alpha_mode() == AlphaMode::Blend && alpha < 1.0 {
+        Arc::new(Dielectric::new(1.5))
+    } else if pbr.metallic_factor() >= 0.5 {
+        Arc::new(Metal::new(base_color, pbr.roughness_factor().into()))
+    } else {
+        Arc::new(Lambertian::new(base_color))
+    }
+}
+
+struct Importer {
+    /// The mapped materials by their index in the document.
+    materials: Vec<Arc<dyn Material>>,
+    default_material: Arc<dyn Material>,
+    buffers: Vec<buffer::Data>,
+    scene: Scene,
+}
+
+impl Importer {
+    fn visit(&mut self, node: &Node<'_>, parent: &Mat4) -> Result<()> {
+        // glTF matrices are column-major
+        let columns = node.transform().matrix();
+        let transform =
+            *parent * Mat4::new(columns.map(|column| column.map(f64::from))).transpose();
+
+        if let Some(mesh) = node.mesh() {
+            let name = node.name().or_else(|| mesh.name()).unwrap_or("default");
+
+            for primitive in mesh.primitives() {
+                if let Some(mesh) = self.primitive_mesh(&primitive, &transform)? {
+                    self.scene.groups.push(Group {
+                        name: name.to_string(),
+                        mesh,
+                    });
+                }
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            self.scene.cameras.push(map_camera(&camera, &transform));
+        }
+
+        for child in node.children() {
+            self.visit(&child, &transform)?;
+        }
+
+        Ok(())
+    }
+
+    /// Points and lines have no surface, so they don't make up a mesh.
+    fn primitive_mesh(
+        &self,
+        primitive: &::gltf::Primitive<'_>,
+        transform: &Mat4,
+    ) -> Result<Option<TriangleMesh>> {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+        let positions: Vec<Vec3> = reader
+            .read_positions()
+            .ok_or_else(|| invalid_data("Primitive without positions"))?
+            .map(|position| transform.transform_point(to_vec3(position)))
+            .collect();
+
+        let vertices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|index| index as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        if vertices.iter().any(|&index| index >= positions.len()) {
+            return Err(invalid_data("Vertex index out of bounds"));
+        }
+
+        let mut indices: Vec<[usize; 3]> = match primitive.mode() {
+            Mode::Triangles => vertices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            Mode::TriangleStrip => (2..vertices.len())
+                .map(|i| match i % 2 {
+                    0 => [vertices[i - 2], vertices[i - 1], vertices[i]],
+                    _ => [vertices[i - 1], vertices[i - 2], vertices[i]],
+                })
+                .collect(),
+            Mode::TriangleFan => (2..vertices.len())
+                .map(|i| [vertices[0], vertices[i - 1], vertices[i]])
+                .collect(),
+            _ => return Ok(None),
+        };
+        // mirroring transforms turn the triangles inside out
+        if transform.determinant() < 0.0 {
+            indices.iter_mut().for_each(|triangle| triangle.swap(1, 2));
+        }
+
+        let material = primitive
+            .material()
+            .index()
+            .map_or(&self.default_material, |index| &self.materials[index]);
+        let mut mesh = TriangleMesh::new(positions, indices, material.clone());
+
+        if let Some(normals) = reader.read_normals() {
+            let normals: Vec<Vec3> = normals
+                .map(|normal| transform.transform_normal(to_vec3(normal)))
+                .collect();
+            if normals.len() != mesh.positions().len() {
+                return Err(invalid_data("Every vertex needs a normal"));
+            }
+            mesh = mesh.with_normals(normals);
+        }
+
+        if let Some(texcoords) = reader.read_tex_coords(0) {
+            let texcoords: Vec<[f64; 2]> = texcoords
+                .into_f32()
+                // glTF puts the origin of the texture at its top left corner, the
+                // textures here at the bottom left one
+                .map(|[u, v]| [u.into(), 1.0 - f64::from(v)])
+                .collect();
+            if texcoords.len() != mesh.positions().len() {
+                return Err(invalid_data("Every vertex needs a texture coordinate"));
+            }
+            mesh = mesh.with_texcoords(texcoords);
+        }
+
+        Ok(Some(mesh))
+    }
+}
+
+/// glTF cameras look towards -Z with +Y up in the space of their node.
+fn map_camera(camera: &::gltf::Camera<'_>, transform: &Mat4) -> Camera {
+    let look_from = transform.transform_point(Vec3::new(0.0, 0.0, 0.0));
+    let look_at = transform.transform_point(Vec3::new(0.0, 0.0, -1.0));
+    let view_up = transform.transform_vector(Vec3::new(0.0, 1.0, 0.0));
+
+    match camera.projection() {
+        Projection::Perspective(perspective) => Camera::look_at(
+            look_from,
+            look_at,
+            view_up,
+            f64::from(perspective.yfov()).to_degrees(),
+            perspective.aspect_ratio().map_or(DEFAULT_ASPECT, f64::from),
+        ),
+        Projection::Orthographic(orthographic) => Camera::orthographic(
+            look_from,
+            look_at,
+            view_up,
+            2.0 * f64::from(orthographic.ymag()),
+            f64::from(orthographic.xmag() / orthographic.ymag()),
+        ),
+    }
+}
+
+fn to_vec3([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3::new(x.into(), y.into(), z.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable::Hitable;
+    use crate::object::face_normal;
+    use crate::testing::assert_vec3_near;
+    use crate::testing::TempDir;
+    use crate::Ray;
+    use rand::thread_rng;
+
+    /// A red unit triangle in the XY plane, scaled by its node and moved away from a
+    /// camera which looks at it from the origin.
+    const SCENE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 2] }],
+        "nodes": [
+            { "name": "root", "translation": [0, 0, -2], "children": [1] },
+            { "name": "triangle", "mesh": 0, "scale": [2, 2, 2] },
+            { "camera": 0, "translation": [0, 0, 1] }
+        ],
+        "meshes": [{
+            "primitives": [{
+                "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 3 },
+                "indices": 2,
+                "material": 0
+            }]
+        }],
+        "materials": [{
+            "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0 },
+            "emissiveFactor": [1, 0.5, 0]
+        }],
+        "cameras": [{
+            "type": "perspective",
+            "perspective": { "yfov": 1.5707963, "aspectRatio": 2, "znear": 0.1 }
+        }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" },
+            { "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" },
+            { "bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC2" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 72, "byteLength": 6 },
+            { "buffer": 0, "byteOffset": 80, "byteLength": 24 }
+        ],
+        BUFFER
+    }"#;
+
+    fn buffer() -> Vec<u8> {
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let normals = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+        let mut bytes: Vec<u8> = positions
+            .iter()
+            .chain(&normals)
+            .flat_map(|value: &f32| value.to_le_bytes().to_vec())
+            .collect();
+        bytes.extend(
+            [0_u16, 1, 2, 0]
+                .iter()
+                .flat_map(|index| index.to_le_bytes().to_vec()),
+        );
+        let texcoords = [0.0, 0.0, 1.0, 0.0, 0.0, 0.25];
+        bytes.extend(
+            texcoords
+                .iter()
+                .flat_map(|value: &f32| value.to_le_bytes().to_vec()),
+        );
+
+        bytes
+    }
+
+    fn glb(scene: &str) -> Vec<u8> {
+        let mut json = scene
+            .replace("BUFFER", r#""buffers": [{ "byteLength": 104 }]"#)
+            .into_bytes();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let bin = buffer();
+
+        let mut glb = vec![];
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2_u32.to_le_bytes());
+        glb.extend_from_slice(&(12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+
+        glb
+    }
+
+    #[test]
+    fn it_bakes_the_node_transforms_into_the_meshes() {
+        let scene = parse(&glb(SCENE)[..]).expect("Should parse");
+
+        assert_eq!(1, scene.groups.len());
+        let group = &scene.groups[0];
+        assert_eq!("triangle", group.name);
+        assert_eq!(
+            vec![
+                Vec3::new(0.0, 0.0, -2.0),
+                Vec3::new(2.0, 0.0, -2.0),
+                Vec3::new(0.0, 2.0, -2.0),
+            ],
+            group.mesh.positions()
+        );
+        assert_eq!(vec![[0, 1, 2]], group.mesh.indices());
+        // the texture coordinates are flipped to start at the bottom
+        assert_eq!(
+            vec![[0.0, 1.0], [1.0, 1.0], [0.0, 0.75]],
+            group.mesh.texcoords()
+        );
+
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = group.mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!(2.0, hit_record.t);
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+        // the emissive material is a light
+        assert_eq!(
+            Vec3::new(1.0, 0.5, 0.0),
+            hit_record.material.emitted(&ray, &hit_record)
+        );
+    }
+
+    #[test]
+    fn it_maps_the_cameras() {
+        let mut rng = thread_rng();
+        let scene = parse(&glb(SCENE)[..]).expect("Should parse");
+
+        assert_eq!(1, scene.cameras.len());
+        let camera = &scene.cameras[0];
+
+        let center = camera
+            .get_ray(0.5, 0.5, &mut rng)
+            .expect("Should be in the image");
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), center.origin);
+        assert_vec3_near(Vec3::new(0.0, 0.0, -1.0), center.direction, 1e-6);
+
+        // 90 degrees vertically with twice the width
+        let upper_right = camera
+            .get_ray(1.0, 1.0, &mut rng)
+            .expect("Should be in the image");
+        assert_vec3_near(Vec3::new(2.0, 1.0, -1.0), upper_right.direction, 1e-6);
+    }
+
+    #[test]
+    fn mirroring_keeps_the_triangles_facing_out() {
+        let mirrored = SCENE.replace(r#""scale": [2, 2, 2]"#, r#""scale": [-2, 2, 2]"#);
+        let scene = parse(&glb(&mirrored)[..]).expect("Should parse");
+        let mesh = &scene.groups[0].mesh;
+
+        assert_eq!(vec![[0, 2, 1]], mesh.indices());
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), mesh.normals()[0]);
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::unit_vector(face_normal(mesh.vertices(0)))
+        );
+    }
+
+    #[test]
+    fn it_loads_external_buffers_next_to_the_gltf_file() {
+        let directory = TempDir::new("it_loads_external_buffers_next_to_the_gltf_file");
+        let gltf = SCENE.replace(
+            "BUFFER",
+            r#""buffers": [{ "byteLength": 104, "uri": "triangle.bin" }]"#,
+        );
+        std::fs::write(directory.join("triangle.gltf"), gltf).expect("Should write the glTF");
+        std::fs::write(directory.join("triangle.bin"), buffer()).expect("Should write the buffer");
+
+        let scene = load(directory.join("triangle.gltf")).expect("Should load");
+        assert_eq!(1, scene.groups.len());
+        assert_eq!(1, scene.cameras.len());
+
+        std::fs::remove_file(directory.join("triangle.bin")).expect("Should remove the buffer");
+        assert!(load(directory.join("triangle.gltf")).is_err());
+    }
+
+    #[test]
+    fn it_rejects_invalid_files() {
+        assert!(parse(&b"glTF"[..]).is_err());
+        assert!(parse(&b"{}"[..]).is_err());
+
+        let error = parse(SCENE.replace("BUFFER", r#""buffers": []"#).as_bytes())
+            .err()
+            .expect("Should fail");
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+}
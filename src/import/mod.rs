@@ -0,0 +1,37 @@
+//! Loaders for meshes and materials exported by other applications.
+
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+use crate::material::{Lambertian, Material};
+use crate::mesh::TriangleMesh;
+use crate::Vec3;
+
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+
+/// The triangles of a named group (`g` or `o` in OBJ, a node in glTF) sharing a
+/// single material.
+pub struct Group {
+    pub name: String,
+    pub mesh: TriangleMesh,
+}
+
+/// The material of the triangles for which the file doesn't define one.
+fn default_material() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.8)))
+}
+
+/// Error for malformed files.
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Error for malformed files, pointing at the (1-based) line with the problem.
+fn invalid_line(line: usize, message: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Line {}: {}", line, message),
+    )
+}
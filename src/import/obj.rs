@@ -0,0 +1,491 @@
+//! Wavefront `.obj` meshes and their `.mtl` material libraries.
+//!
+//! Polygons are triangulated as fans and every group is split into one mesh per
+//! material. The MTL materials are mapped onto the closest material of the crate:
+//!
+//! * emissive (`Ke`) materials become a `DiffuseLight`
+//! * transparent (`d`/`Tr`, `illum` 4, 6, 7 or 9) materials become a `Dielectric`
+//!   with the `Ni` index of refraction
+//! * reflective (`illum` 3, 5 or 8) materials become a `Metal` with the `Ks` color,
+//!   the lower the `Ns` exponent the fuzzier the reflection
+//! * everything else becomes a `Lambertian` with the `Kd` color
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{default_material, invalid_line, Group};
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::TriangleMesh;
+use crate::Vec3;
+
+/// Loads the `.obj` file and the material libraries it references, which are
+/// looked up relative to the `.obj` file.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Group>> {
+    let path = path.as_ref();
+    let obj = parse_statements(BufReader::new(File::open(path)?))?;
+
+    let mut materials = HashMap::new();
+    for library in &obj.material_libraries {
+        let library_path = path.with_file_name(library);
+        materials.extend(parse_mtl(BufReader::new(File::open(library_path)?))?);
+    }
+
+    Ok(obj.into_groups(&materials))
+}
+
+/// Parses the `.obj` statements, ignoring any `mtllib` and looking up the `usemtl`
+/// names in the `materials` instead.
+pub fn parse<R: BufRead>(
+    reader: R,
+    materials: &HashMap<String, Arc<dyn Material>>,
+) -> Result<Vec<Group>> {
+    Ok(parse_statements(reader)?.into_groups(materials))
+}
+
+/// Parses a `.mtl` material library into materials by their names.
+pub fn parse_mtl<R: BufRead>(reader: R) -> Result<HashMap<String, Arc<dyn Material>>> {
+    let mut definitions: Vec<(String, MtlDefinition)> = vec![];
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            let name = arguments.join(" ");
+            definitions.push((name, MtlDefinition::default()));
+            continue;
+        }
+
+        let definition = match definitions.last_mut() {
+            Some((_, definition)) => definition,
+            None => return Err(invalid_line(line_number, "Statement before `newmtl`")),
+        };
+
+        match keyword {
+            "Kd" => definition.diffuse = parse_vec3(&arguments, line_number)?,
+            "Ks" => definition.specular = parse_vec3(&arguments, line_number)?,
+            "Ke" => definition.emissive = parse_vec3(&arguments, line_number)?,
+            "Ns" => definition.shininess = parse_f64(&arguments, 0, line_number)?,
+            "Ni" => definition.optical_density = parse_f64(&arguments, 0, line_number)?,
+            "d" => definition.dissolve = parse_f64(&arguments, 0, line_number)?,
+            "Tr" => definition.dissolve = 1.0 - parse_f64(&arguments, 0, line_number)?,
+            "illum" => definition.illumination = parse_f64(&arguments, 0, line_number)? as u32,
+            // texture maps and the rest of the statements are not supported
+            _ => {}
+        }
+    }
+
+    Ok(definitions
+        .into_iter()
+        .map(|(name, definition)| (name, definition.into_material()))
+        .collect())
+}
+
+struct MtlDefinition {
+    diffuse: Vec3,
+    specular: Vec3,
+    emissive: Vec3,
+    shininess: f64,
+    optical_density: f64,
+    dissolve: f64,
+    illumination: u32,
+}
+
+impl Default for MtlDefinition {
+    fn default() -> Self {
+        Self {
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            emissive: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            optical_density: 1.5,
+            dissolve: 1.0,
+            illumination: 2,
+        }
+    }
+}
+
+impl MtlDefinition {
+    fn into_material(self) -> Arc<dyn Material> {
+        let is_emissive = self.emissive.squared_length() > 0.0;
+        let is_transparent = self.dissolve < 1.0 || [4, 6, 7, 9].contains(&self.illumination);
+        let is_reflective = [3, 5, 8].contains(&self.illumination);
+
+        if is_emissive {
+            Arc::new(DiffuseLight::new(self.emissive))
+        } else if is_transparent {
+            Arc::new(Dielectric::new(self.optical_density))
+        } else if is_reflective {
+            // maps the Phong exponent onto the roughness of the surface
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt();
+
+            Arc::new(Metal::new(self.specular, fuzz))
+        } else {
+            Arc::new(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+/// The indices of the position, texture coordinate and normal of a face vertex.
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+/// Triangles sharing a group and a material.
+struct FaceGroup {
+    name: String,
+    material: Option<String>,
+    triangles: Vec<[FaceVertex; 3]>,
+}
+
+#[derive(Default)]
+struct ObjStatements {
+    positions: Vec<Vec3>,
+    texcoords: Vec<[f64; 2]>,
+    normals: Vec<Vec3>,
+    groups: Vec<FaceGroup>,
+    material_libraries: Vec<String>,
+}
+
+fn parse_statements<R: BufRead>(reader: R) -> Result<ObjStatements> {
+    let mut obj = ObjStatements::default();
+    let mut group_name = String::from("default");
+    let mut material = None;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let arguments: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => obj.positions.push(parse_vec3(&arguments, line_number)?),
+            "vn" => obj.normals.push(parse_vec3(&arguments, line_number)?),
+            "vt" => {
+                let u = parse_f64(&arguments, 0, line_number)?;
+                let v = if arguments.len() > 1 {
+                    parse_f64(&arguments, 1, line_number)?
+                } else {
+                    0.0
+                };
+                obj.texcoords.push([u, v]);
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(invalid_line(line_number, "Faces need at least 3 vertices"));
+                }
+                let vertices = arguments
+                    .iter()
+                    .map(|vertex| obj.parse_face_vertex(vertex, line_number))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let triangles = obj.face_group(&group_name, &material);
+                for i in 1..vertices.len() - 1 {
+                    triangles.push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+            }
+            "g" | "o" => group_name = arguments.join(" "),
+            "usemtl" => material = Some(arguments.join(" ")),
+            "mtllib" => obj
+                .material_libraries
+                .extend(arguments.iter().map(|library| library.to_string())),
+            // smoothing groups, lines, points and the rest are not supported
+            _ => {}
+        }
+    }
+
+    Ok(obj)
+}
+
+impl ObjStatements {
+    /// Parses `position[/texcoord][/normal]`, where negative indices count
+    /// backwards from the last element.
+    fn parse_face_vertex(&self, vertex: &str, line_number: usize) -> Result<FaceVertex> {
+        let mut indices = vertex.split('/');
+        let resolve = |index: Option<&str>, len: usize| -> Result<Option<usize>> {
+            let index = match index {
+                Some(index) if !index.is_empty() => index,
+                _ => return Ok(None),
+            };
+            let index: i64 = index
+                .parse()
+                .map_err(|_| invalid_line(line_number, "Invalid face index"))?;
+            let resolved = if index < 0 {
+                len as i64 + index
+            } else {
+                index - 1
+            };
+
+            if resolved < 0 || resolved >= len as i64 {
+                return Err(invalid_line(line_number, "Face index out of bounds"));
+            }
+
+            Ok(Some(resolved as usize))
+        };
+
+        let position = resolve(indices.next(), self.positions.len())?
+            .ok_or_else(|| invalid_line(line_number, "Face vertex without a position"))?;
+        let texcoord = resolve(indices.next(), self.texcoords.len())?;
+        let normal = resolve(indices.next(), self.normals.len())?;
+
+        Ok((position, texcoord, normal))
+    }
+
+    fn face_group(&mut self, name: &str, material: &Option<String>) -> &mut Vec<[FaceVertex; 3]> {
+        let index = self
+            .groups
+            .iter()
+            .position(|group| group.name == name && &group.material == material);
+
+        let index = index.unwrap_or_else(|| {
+            self.groups.push(FaceGroup {
+                name: name.to_string(),
+                material: material.clone(),
+                triangles: vec![],
+            });
+            self.groups.len() - 1
+        });
+
+        &mut self.groups[index].triangles
+    }
+
+    fn into_groups(self, materials: &HashMap<String, Arc<dyn Material>>) -> Vec<Group> {
+        let default_material = default_material();
+
+        self.groups
+            .iter()
+            .map(|group| {
+                let material = group
+                    .material
+                    .as_ref()
+                    .and_then(|name| materials.get(name))
+                    .cloned()
+                    .unwrap_or_else(|| default_material.clone());
+
+                Group {
+                    name: group.name.clone(),
+                    mesh: self.build_mesh(group, material),
+                }
+            })
+            .collect()
+    }
+
+    /// Every distinct combination of position, texture coordinate and normal
+    /// becomes a vertex of the mesh.
+    fn build_mesh(&self, group: &FaceGroup, material: Arc<dyn Material>) -> TriangleMesh {
+        let mut vertices: HashMap<FaceVertex, usize> = HashMap::new();
+        let mut unique: Vec<FaceVertex> = vec![];
+
+        let indices = group
+            .triangles
+            .iter()
+            .map(|triangle| {
+                let mut indices = [0; 3];
+                for (index, vertex) in indices.iter_mut().zip(triangle) {
+                    *index = *vertices.entry(*vertex).or_insert_with(|| {
+                        unique.push(*vertex);
+                        unique.len() - 1
+                    });
+                }
+                indices
+            })
+            .collect();
+
+        let positions = unique
+            .iter()
+            .map(|(position, _, _)| self.positions[*position]);
+        let mut mesh = TriangleMesh::new(positions.collect(), indices, material);
+
+        // the attributes are only used when every vertex has them
+        let texcoords: Option<Vec<[f64; 2]>> = unique
+            .iter()
+            .map(|(_, texcoord, _)| texcoord.map(|texcoord| self.texcoords[texcoord]))
+            .collect();
+        if let Some(texcoords) = texcoords {
+            mesh = mesh.with_texcoords(texcoords);
+        }
+
+        let normals: Option<Vec<Vec3>> = unique
+            .iter()
+            .map(|(_, _, normal)| normal.map(|normal| self.normals[normal]))
+            .collect();
+        if let Some(normals) = normals {
+            mesh = mesh.with_normals(normals);
+        }
+
+        mesh
+    }
+}
+
+fn parse_f64(arguments: &[&str], index: usize, line_number: usize) -> Result<f64> {
+    arguments
+        .get(index)
+        .and_then(|argument| argument.parse().ok())
+        .ok_or_else(|| invalid_line(line_number, "Expected a number"))
+}
+
+fn parse_vec3(arguments: &[&str], line_number: usize) -> Result<Vec3> {
+    Ok(Vec3::new(
+        parse_f64(arguments, 0, line_number)?,
+        parse_f64(arguments, 1, line_number)?,
+        parse_f64(arguments, 2, line_number)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable::Hitable;
+    use crate::testing::TempDir;
+    use crate::Ray;
+
+    const CUBE_SIDES: &str = "
+# two sides of a cube
+mtllib cube.mtl
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+v 1 -1 -1
+v 1 1 -1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+vn 1 0 0
+
+g front
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g right
+usemtl mirror
+f -5//2 -2//2 -1//2
+f 2//2 6//2 3//2
+";
+
+    const CUBE_MTL: &str = "
+newmtl red
+Kd 0.8 0.1 0.1
+
+newmtl mirror
+illum 3
+Ks 0.9 0.9 0.9
+Ns 1000
+
+newmtl glass
+illum 7
+Ni 1.33
+
+newmtl lamp
+Ke 10 10 10
+";
+
+    #[test]
+    fn it_parses_groups_and_triangulates_polygons() {
+        let materials = parse_mtl(CUBE_MTL.as_bytes()).expect("Should parse the MTL");
+        let groups = parse(CUBE_SIDES.as_bytes(), &materials).expect("Should parse the OBJ");
+
+        assert_eq!(2, groups.len());
+
+        let front = &groups[0];
+        assert_eq!("front", front.name);
+        assert_eq!(2, front.mesh.len());
+        assert_eq!(4, front.mesh.positions().len());
+        assert_eq!(
+            vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            front.mesh.texcoords()
+        );
+        assert_eq!(vec![Vec3::new(0.0, 0.0, 1.0); 4], front.mesh.normals());
+        assert!(Arc::ptr_eq(&materials["red"], &front.mesh.material));
+
+        let right = &groups[1];
+        assert_eq!("right", right.name);
+        assert_eq!(2, right.mesh.len());
+        // the negative indices point to the same vertices as the positive ones
+        assert_eq!(4, right.mesh.positions().len());
+        assert!(right.mesh.texcoords().is_empty());
+        assert!(Arc::ptr_eq(&materials["mirror"], &right.mesh.material));
+    }
+
+    #[test]
+    fn parsed_meshes_can_be_hit() {
+        let groups = parse(CUBE_SIDES.as_bytes(), &HashMap::new()).expect("Should parse");
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let hit_record = groups[0].mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!(4.0, hit_record.t);
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+    }
+
+    #[test]
+    fn it_maps_mtl_materials() {
+        let materials = parse_mtl(CUBE_MTL.as_bytes()).expect("Should parse the MTL");
+        assert_eq!(4, materials.len());
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let lamp = &materials["lamp"];
+        let hit_record = crate::hitable::HitRecord::new(
+            1.0,
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            &**lamp,
+        );
+        assert_eq!(Vec3::new(10.0, 10.0, 10.0), lamp.emitted(&ray, &hit_record));
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            materials["red"].emitted(&ray, &hit_record)
+        );
+    }
+
+    #[test]
+    fn it_loads_the_material_libraries_next_to_the_obj_file() {
+        let directory = TempDir::new("it_loads_the_material_libraries_next_to_the_obj_file");
+        std::fs::write(directory.join("cube.obj"), CUBE_SIDES).expect("Should write the OBJ");
+        std::fs::write(directory.join("cube.mtl"), CUBE_MTL).expect("Should write the MTL");
+
+        let groups = load(directory.join("cube.obj")).expect("Should load");
+
+        // the right side is a mirror instead of the default diffuse material
+        let ray = Ray::new(Vec3::new(5.0, 0.5, 0.5), Vec3::new(-1.0, 0.0, 0.0));
+        let hit_record = groups[1].mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert!(hit_record
+            .material
+            .eval(&ray, &hit_record, Vec3::new(1.0, 0.0, 0.0))
+            .is_none());
+
+        std::fs::remove_file(directory.join("cube.mtl")).expect("Should remove the MTL");
+        assert!(load(directory.join("cube.obj")).is_err());
+    }
+
+    #[test]
+    fn it_rejects_invalid_files() {
+        let materials = HashMap::new();
+
+        let out_of_bounds = "v 0 0 0\nv 1 0 0\nf 1 2 3\n";
+        let error = parse(out_of_bounds.as_bytes(), &materials)
+            .err()
+            .expect("Should fail");
+        assert_eq!("Line 3: Face index out of bounds", error.to_string());
+
+        let invalid_number = "v 0 zero 0\n";
+        assert!(parse(invalid_number.as_bytes(), &materials).is_err());
+
+        let line = "v 0 0 0\nv 1 0 0\nf 1 2\n";
+        assert!(parse(line.as_bytes(), &materials).is_err());
+
+        assert!(parse_mtl("Kd 1 1 1\n".as_bytes()).is_err());
+    }
+}
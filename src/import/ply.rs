@@ -0,0 +1,484 @@
+//! Stanford `.ply` meshes, in both the ASCII and the binary encodings.
+//!
+//! Only the `vertex` elements (positions with optional normals and texture
+//! coordinates) and the `face` elements (polygons triangulated as fans) are used,
+//! any other element is skipped.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Result};
+use std::path::Path;
+use std::str::SplitWhitespace;
+use std::sync::Arc;
+
+use super::{default_material, invalid_data, invalid_line};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::Vec3;
+
+/// Loads the `.ply` file as a mesh with a grey diffuse material.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<TriangleMesh> {
+    parse(BufReader::new(File::open(path)?), default_material())
+}
+
+/// Parses the `.ply` header and the elements following it.
+pub fn parse<R: BufRead>(mut reader: R, material: Arc<dyn Material>) -> Result<TriangleMesh> {
+    let header = parse_header(&mut reader)?;
+
+    let elements = match header.format {
+        Format::Ascii => {
+            let mut body = String::new();
+            reader.read_to_string(&mut body)?;
+
+            read_elements(&header, &mut AsciiValues(body.split_whitespace()))?
+        }
+        Format::BinaryLittleEndian => read_elements(
+            &header,
+            &mut BinaryValues {
+                reader,
+                big_endian: false,
+            },
+        )?,
+        Format::BinaryBigEndian => read_elements(
+            &header,
+            &mut BinaryValues {
+                reader,
+                big_endian: true,
+            },
+        )?,
+    };
+
+    elements.into_mesh(material)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::Int8,
+            "uchar" | "uint8" => Scalar::UInt8,
+            "short" | "int16" => Scalar::Int16,
+            "ushort" | "uint16" => Scalar::UInt16,
+            "int" | "int32" => Scalar::Int32,
+            "uint" | "uint32" => Scalar::UInt32,
+            "float" | "float32" => Scalar::Float32,
+            "double" | "float64" => Scalar::Float64,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Property {
+    Scalar(String, Scalar),
+    /// A count followed by that many items.
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar(name, _) | Property::List(name, _, _) => name,
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    /// The index of the first property with one of the `names`.
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name()))
+    }
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+fn parse_header<R: BufRead>(reader: &mut R) -> Result<Header> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut line = String::new();
+
+    for line_number in 1.. {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_line(line_number, "Expected end_header"));
+        }
+
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        let arguments: Vec<&str> = words.collect();
+
+        if line_number == 1 {
+            if keyword != Some("ply") {
+                return Err(invalid_line(line_number, "Expected ply"));
+            }
+            continue;
+        }
+
+        match (keyword, arguments.as_slice()) {
+            (Some("format"), [encoding, _version]) => {
+                format = Some(match *encoding {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid_line(line_number, "Unknown format")),
+                });
+            }
+            (Some("element"), [name, count]) => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid_line(line_number, "Expected a count"))?,
+                properties: vec![],
+            }),
+            (Some("property"), arguments) => {
+                let property = parse_property(arguments)
+                    .ok_or_else(|| invalid_line(line_number, "Invalid property"))?;
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_line(line_number, "Property without an element"))?
+                    .properties
+                    .push(property);
+            }
+            (Some("end_header"), _) => break,
+            (Some("comment"), _) | (Some("obj_info"), _) | (None, _) => {}
+            _ => return Err(invalid_line(line_number, "Unknown header statement")),
+        }
+    }
+
+    Ok(Header {
+        format: format.ok_or_else(|| invalid_data("Missing format"))?,
+        elements,
+    })
+}
+
+fn parse_property(arguments: &[&str]) -> Option<Property> {
+    match arguments {
+        ["list", count, item, name] => Some(Property::List(
+            name.to_string(),
+            Scalar::parse(count)?,
+            Scalar::parse(item)?,
+        )),
+        [scalar, name] => Some(Property::Scalar(name.to_string(), Scalar::parse(scalar)?)),
+        _ => None,
+    }
+}
+
+trait Values {
+    fn next(&mut self, scalar: Scalar) -> Result<f64>;
+}
+
+struct AsciiValues<'a>(SplitWhitespace<'a>);
+
+impl Values for AsciiValues<'_> {
+    fn next(&mut self, _scalar: Scalar) -> Result<f64> {
+        self.0
+            .next()
+            .ok_or_else(|| invalid_data("Unexpected end of file"))?
+            .parse()
+            .map_err(|_| invalid_data("Expected a number"))
+    }
+}
+
+struct BinaryValues<R> {
+    reader: R,
+    big_endian: bool,
+}
+
+impl<R: Read> BinaryValues<R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> Values for BinaryValues<R> {
+    fn next(&mut self, scalar: Scalar) -> Result<f64> {
+        macro_rules! decode {
+            ($type:ty) => {{
+                let bytes = self.bytes()?;
+                if self.big_endian {
+                    <$type>::from_be_bytes(bytes)
+                } else {
+                    <$type>::from_le_bytes(bytes)
+                }
+            }};
+        }
+
+        Ok(match scalar {
+            Scalar::Int8 => decode!(i8).into(),
+            Scalar::UInt8 => decode!(u8).into(),
+            Scalar::Int16 => decode!(i16).into(),
+            Scalar::UInt16 => decode!(u16).into(),
+            Scalar::Int32 => decode!(i32).into(),
+            Scalar::UInt32 => decode!(u32).into(),
+            Scalar::Float32 => decode!(f32).into(),
+            Scalar::Float64 => decode!(f64),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Elements {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    texcoords: Vec<[f64; 2]>,
+    /// Polygons of any size.
+    faces: Vec<Vec<f64>>,
+}
+
+fn read_elements(header: &Header, values: &mut dyn Values) -> Result<Elements> {
+    let mut elements = Elements::default();
+    let mut scalars = vec![];
+    let mut face = vec![];
+
+    for element in &header.elements {
+        let position = [
+            element.property(&["x"]),
+            element.property(&["y"]),
+            element.property(&["z"]),
+        ];
+        let normal = [
+            element.property(&["nx"]),
+            element.property(&["ny"]),
+            element.property(&["nz"]),
+        ];
+        let texcoord = [
+            element.property(&["u", "s", "texture_u", "texture_s"]),
+            element.property(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let vertex_indices = element.property(&["vertex_indices", "vertex_index"]);
+
+        for _ in 0..element.count {
+            scalars.clear();
+            for (index, property) in element.properties.iter().enumerate() {
+                match *property {
+                    Property::Scalar(_, scalar) => scalars.push(values.next(scalar)?),
+                    Property::List(_, count, item) => {
+                        let count = values.next(count)?;
+                        let items = (0..count as usize)
+                            .map(|_| values.next(item))
+                            .collect::<Result<Vec<f64>>>()?;
+                        if Some(index) == vertex_indices {
+                            face = items;
+                        }
+                        scalars.push(0.0);
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let vec3 = |[x, y, z]: [Option<usize>; 3]| {
+                        Some(Vec3::new(scalars[x?], scalars[y?], scalars[z?]))
+                    };
+
+                    elements.positions.push(
+                        vec3(position).ok_or_else(|| invalid_data("Vertex without a position"))?,
+                    );
+                    elements.normals.extend(vec3(normal));
+                    if let [Some(u), Some(v)] = texcoord {
+                        elements.texcoords.push([scalars[u], scalars[v]]);
+                    }
+                }
+                "face" if vertex_indices.is_some() => {
+                    elements.faces.push(std::mem::take(&mut face));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(elements)
+}
+
+impl Elements {
+    fn into_mesh(self, material: Arc<dyn Material>) -> Result<TriangleMesh> {
+        let vertex_count = self.positions.len();
+        let vertex = |index: f64| {
+            if index >= 0.0 && (index as usize) < vertex_count {
+                Ok(index as usize)
+            } else {
+                Err(invalid_data("Vertex index out of bounds"))
+            }
+        };
+
+        let mut indices = vec![];
+        for face in &self.faces {
+            if face.len() < 3 {
+                return Err(invalid_data("Face with fewer than three vertices"));
+            }
+
+            for i in 2..face.len() {
+                indices.push([vertex(face[0])?, vertex(face[i - 1])?, vertex(face[i])?]);
+            }
+        }
+
+        let mut mesh = TriangleMesh::new(self.positions, indices, material);
+        // the attributes are only used when every vertex has them
+        if self.normals.len() == vertex_count {
+            mesh = mesh.with_normals(self.normals);
+        }
+        if self.texcoords.len() == vertex_count {
+            mesh = mesh.with_texcoords(self.texcoords);
+        }
+
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable::Hitable;
+    use crate::material::Lambertian;
+    use crate::Ray;
+    use std::io::ErrorKind;
+
+    /// A square facing +z, with an extra element which isn't part of the mesh.
+    const SQUARE: &str = "ply
+format ascii 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+-1 -1 -1 0 0 1 0 0
+1 -1 -1 0 0 1 1 0
+1 1 -1 0 0 1 1 1
+-1 1 -1 0 0 1 0 1
+4 0 1 2 3
+0 1
+";
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)))
+    }
+
+    /// The square without the normals and texture coordinates, in binary.
+    fn binary_square(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\nproperty double x\nproperty double y\n\
+             property double z\nelement face 1\nproperty list uchar uint vertex_index\nend_header\n",
+            format
+        )
+        .into_bytes();
+
+        let positions = [
+            -1.0, -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0,
+        ];
+        for &value in &positions {
+            let value: f64 = value;
+            bytes.extend_from_slice(&if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            });
+        }
+        bytes.push(4);
+        for index in 0_u32..4 {
+            bytes.extend_from_slice(&if big_endian {
+                index.to_be_bytes()
+            } else {
+                index.to_le_bytes()
+            });
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn it_parses_ascii_files() {
+        let mesh = parse(SQUARE.as_bytes(), material()).expect("Should parse");
+
+        assert_eq!(4, mesh.positions().len());
+        assert_eq!(Vec3::new(1.0, 1.0, -1.0), mesh.positions()[2]);
+        assert_eq!(vec![[0, 1, 2], [0, 2, 3]], mesh.indices());
+        assert_eq!(vec![Vec3::new(0.0, 0.0, 1.0); 4], mesh.normals());
+        assert_eq!(
+            vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            mesh.texcoords()
+        );
+
+        let ray = Ray::new(Vec3::new(0.5, -0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!(Vec3::new(0.5, -0.5, -1.0), hit_record.p);
+    }
+
+    #[test]
+    fn it_parses_binary_files() {
+        for &big_endian in &[false, true] {
+            let mesh = parse(&binary_square(big_endian)[..], material()).expect("Should parse");
+
+            assert_eq!(Vec3::new(1.0, 1.0, -1.0), mesh.positions()[2]);
+            assert_eq!(vec![[0, 1, 2], [0, 2, 3]], mesh.indices());
+            assert!(mesh.normals().is_empty());
+            assert!(mesh.texcoords().is_empty());
+        }
+    }
+
+    #[test]
+    fn it_rejects_invalid_files() {
+        assert!(parse(&b"obj\n"[..], material()).is_err());
+        assert!(parse(&b"ply\nformat ascii 1.0\n"[..], material()).is_err());
+        assert!(parse(&b"ply\nformat xml 1.0\nend_header\n"[..], material()).is_err());
+
+        let truncated = SQUARE.replace("4 0 1 2 3\n0 1\n", "4 0 1 2");
+        let error = parse(truncated.as_bytes(), material())
+            .err()
+            .expect("Should fail");
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+
+        let out_of_bounds = SQUARE.replace("4 0 1 2 3", "4 0 1 2 4");
+        assert!(parse(out_of_bounds.as_bytes(), material()).is_err());
+
+        let mut truncated = binary_square(true);
+        truncated.pop();
+        assert!(parse(&truncated[..], material()).is_err());
+    }
+}
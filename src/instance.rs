@@ -0,0 +1,522 @@
+//! Copies of a shared hitable placed into the scene with their own transform.
+
+use std::iter::FromIterator;
+use std::sync::{Arc, OnceLock};
+
+use crate::aabb::Aabb;
+use crate::bvh::Tree;
+use crate::hitable::{HitRecord, Hitable};
+use crate::{Mat4, Ray, Vec3};
+
+/// A shared hitable moved into place by a transform, without copying it, so a
+/// single mesh can appear any number of times.
+///
+/// Rays are transformed into the space of the hitable instead of transforming
+/// the hitable, then the hit point and normal are transformed back out.
+pub struct Instance {
+    hitable: Arc<dyn Hitable>,
+    transform: Mat4,
+    inverse: Mat4,
+    motion: Option<Motion>,
+}
+
+/// The instance moves from its transform at `time0` to this `transform` at
+/// `time1`, resting at those ends before and after.
+struct Motion {
+    transform: Mat4,
+    time0: f64,
+    time1: f64,
+}
+
+impl Instance {
+    /// # Panics
+    ///
+    /// If the `transform` can't be inverted, e.g. when scaling by zero.
+    pub fn new(hitable: Arc<dyn Hitable>, transform: Mat4) -> Self {
+        let inverse = transform
+            .inverse()
+            .expect("Instance transform must be invertible");
+
+        Self {
+            hitable,
+            transform,
+            inverse,
+            motion: None,
+        }
+    }
+
+    /// Makes the instance move from its current transform at `time0` to the
+    /// `end` transform at `time1`, interpolating the matrices in between.
+    ///
+    /// # Panics
+    ///
+    /// If the `end` transform can't be inverted.
+    pub fn with_motion(mut self, end: Mat4, time0: f64, time1: f64) -> Self {
+        assert!(
+            end.inverse().is_some(),
+            "Instance transform must be invertible"
+        );
+
+        self.motion = Some(Motion {
+            transform: end,
+            time0,
+            time1,
+        });
+        self
+    }
+
+    /// Moves the instance by the `offset` after its current transform.
+    pub fn translate(self, offset: Vec3) -> Self {
+        self.then(Mat4::translation(offset))
+    }
+
+    /// Rotates the instance by the `angle` (in degrees) around the `axis` through
+    /// the origin after its current transform.
+    pub fn rotate(self, axis: Vec3, angle: f64) -> Self {
+        self.then(Mat4::rotation(axis, angle))
+    }
+
+    /// Scales the instance away from the origin after its current transform.
+    pub fn scale(self, factors: Vec3) -> Self {
+        self.then(Mat4::scaling(factors))
+    }
+
+    pub fn transform(&self) -> Mat4 {
+        self.transform
+    }
+
+    /// Replaces the whole transform, keeping the shared hitable and stopping any
+    /// motion.
+    ///
+    /// # Panics
+    ///
+    /// If the `transform` can't be inverted.
+    pub fn set_transform(&mut self, transform: Mat4) {
+        self.inverse = transform
+            .inverse()
+            .expect("Instance transform must be invertible");
+        self.transform = transform;
+        self.motion = None;
+    }
+
+    /// The shared hitable in its own space.
+    pub fn hitable(&self) -> &Arc<dyn Hitable> {
+        &self.hitable
+    }
+
+    fn then(self, transform: Mat4) -> Self {
+        let motion = self.motion.map(|motion| Motion {
+            transform: transform * motion.transform,
+            ..motion
+        });
+
+        Self {
+            motion,
+            ..Self::new(self.hitable, transform * self.transform)
+        }
+    }
+
+    /// The transform and its inverse at the `time`, `None` when the interpolated
+    /// transform flattens the hitable.
+    fn transform_at(&self, time: f64) -> Option<(Mat4, Mat4)> {
+        let motion = match &self.motion {
+            Some(motion) if motion.time1 > motion.time0 => motion,
+            _ => return Some((self.transform, self.inverse)),
+        };
+
+        let t = ((time - motion.time0) / (motion.time1 - motion.time0)).clamp(0.0, 1.0);
+        let transform = Mat4::lerp(self.transform, motion.transform, t);
+
+        Some((transform, transform.inverse()?))
+    }
+}
+
+impl Hitable for Instance {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (transform, inverse) = self.transform_at(ray.time)?;
+        // the direction isn't normalized, so the distances stay the same
+        let local_ray = Ray::new(
+            inverse.transform_point(ray.origin),
+            inverse.transform_vector(ray.direction),
+        )
+        .with_time(ray.time);
+
+        let hit_record = self.hitable.hit(&local_ray, t_min, t_max)?;
+
+        Some(HitRecord {
+            p: transform.transform_point(hit_record.p),
+            normal: transform.transform_normal(hit_record.normal),
+            ..hit_record
+        })
+    }
+
+    /// Interpolating the matrices moves every point in a straight line, so the
+    /// boxes at both ends of the motion contain all of the others.
+    fn bounding_box(&self) -> Option<Aabb> {
+        let aabb = self.hitable.bounding_box()?;
+        let start = self.transform.transform_aabb(&aabb);
+
+        Some(match &self.motion {
+            Some(motion) => start.union(&motion.transform.transform_aabb(&aabb)),
+            None => start,
+        })
+    }
+}
+
+/// A two-level acceleration structure, a top level BVH over the instances, each
+/// of which shares its geometry along with the bottom level BVH of the geometry
+/// (e.g. a `TriangleMesh` or a `Bvh`).
+///
+/// Moving instances only needs the small top level to be rebuilt, which makes
+/// animating the instances between frames cheap. The top level is rebuilt on the
+/// first hit after any changes.
+pub struct InstancedScene {
+    instances: Vec<Instance>,
+    top_level: OnceLock<TopLevel>,
+}
+
+/// The BVH over the bounding boxes of the instances.
+struct TopLevel {
+    tree: Tree,
+    /// The instances in the `tree` by the index of their bounding box.
+    bounded: Vec<usize>,
+    /// The instances of unbounded geometry, which are tested for every ray.
+    unbounded: Vec<usize>,
+}
+
+impl TopLevel {
+    fn new(instances: &[Instance]) -> Self {
+        let mut bounds = vec![];
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+
+        for (index, instance) in instances.iter().enumerate() {
+            match instance.bounding_box() {
+                Some(aabb) => {
+                    bounds.push(aabb);
+                    bounded.push(index);
+                }
+                None => unbounded.push(index),
+            }
+        }
+
+        Self {
+            tree: Tree::new(&bounds),
+            bounded,
+            unbounded,
+        }
+    }
+}
+
+impl InstancedScene {
+    pub fn new(instances: Vec<Instance>) -> Self {
+        let mut scene = Self {
+            instances,
+            top_level: OnceLock::new(),
+        };
+        scene.rebuild();
+
+        scene
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Adds the `instance`, returning its index.
+    pub fn push(&mut self, instance: Instance) -> usize {
+        self.instances.push(instance);
+        self.top_level = OnceLock::new();
+
+        self.instances.len() - 1
+    }
+
+    /// Moves the instance at `index`.
+    ///
+    /// # Panics
+    ///
+    /// If the `transform` can't be inverted.
+    pub fn set_transform(&mut self, index: usize, transform: Mat4) {
+        self.instances[index].set_transform(transform);
+        self.top_level = OnceLock::new();
+    }
+
+    /// Rebuilds the top level BVH over the current instance bounding boxes,
+    /// leaving the shared geometry untouched.
+    ///
+    /// This happens on the next hit anyway, rebuilding up front just keeps it out
+    /// of the rendering.
+    pub fn rebuild(&mut self) {
+        self.top_level = OnceLock::from(TopLevel::new(&self.instances));
+    }
+
+    fn top_level(&self) -> &TopLevel {
+        self.top_level
+            .get_or_init(|| TopLevel::new(&self.instances))
+    }
+}
+
+impl FromIterator<Instance> for InstancedScene {
+    fn from_iter<I: IntoIterator<Item = Instance>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl Hitable for InstancedScene {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let top_level = self.top_level();
+
+        let closest = top_level
+            .tree
+            .hit(ray, t_min, t_max, |index, closest_so_far| {
+                self.instances[top_level.bounded[index]].hit(ray, t_min, closest_so_far)
+            });
+
+        top_level.unbounded.iter().fold(closest, |closest, &index| {
+            let closest_so_far = closest.as_ref().map_or(t_max, |hr| hr.t);
+
+            self.instances[index]
+                .hit(ray, t_min, closest_so_far)
+                .or(closest)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let top_level = self.top_level();
+
+        if top_level.unbounded.is_empty() {
+            Some(top_level.tree.bounding_box())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::hitable::HitableList;
+    use crate::material::Lambertian;
+    use crate::mesh::TriangleMesh;
+    use crate::object::{Quad, Sphere};
+    use crate::testing::assert_vec3_eq;
+
+    fn unit_sphere() -> Arc<dyn Hitable> {
+        Arc::new(Sphere::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    #[test]
+    fn it_hits_the_moved_hitable() {
+        let instance = Instance::new(unit_sphere(), Mat4::identity())
+            .scale(Vec3::new(2.0, 2.0, 2.0))
+            .translate(Vec3::new(0.0, 0.0, -5.0));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let hit_record = instance.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!(3.0, hit_record.t);
+        assert_eq!(Vec3::new(0.0, 0.0, -3.0), hit_record.p);
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+
+        let missing = Ray::new(Vec3::new(2.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(instance.hit(&missing, 0.0, 10.0).is_none());
+    }
+
+    #[test]
+    fn it_rotates_the_normals() {
+        let quad: Arc<dyn Hitable> = Arc::new(Quad::new(
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        ));
+        // the quad now faces +x instead of +z
+        let instance = Instance::new(quad, Mat4::identity())
+            .rotate(Vec3::new(0.0, 1.0, 0.0), 90.0)
+            .translate(Vec3::new(-3.0, 0.0, 0.0));
+        let ray = Ray::new(Vec3::new(0.0, 0.5, 0.5), Vec3::new(-1.0, 0.0, 0.0));
+
+        let hit_record = instance.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert!((hit_record.t - 3.0).abs() < 1e-10);
+        assert_vec3_eq(Vec3::new(-3.0, 0.5, 0.5), hit_record.p);
+        assert_vec3_eq(Vec3::new(1.0, 0.0, 0.0), hit_record.normal);
+    }
+
+    #[test]
+    fn it_transforms_the_bounding_box() {
+        let instance = Instance::new(unit_sphere(), Mat4::translation(Vec3::new(1.0, 2.0, 3.0)));
+
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(0.0, 1.0, 2.0),
+                Vec3::new(2.0, 3.0, 4.0)
+            )),
+            instance.bounding_box()
+        );
+    }
+
+    #[test]
+    fn moving_instances_are_hit_where_they_are_at_the_time_of_the_ray() {
+        let instance = Instance::new(unit_sphere(), Mat4::translation(Vec3::new(0.0, 0.0, -5.0)))
+            .with_motion(Mat4::translation(Vec3::new(4.0, 0.0, -5.0)), 0.0, 1.0)
+            .scale(Vec3::new(0.5, 0.5, 0.5));
+        let ray = Ray::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(instance.hit(&ray, 0.0, 10.0).is_none());
+        let hit_record = instance
+            .hit(&ray.with_time(0.5), 0.0, 10.0)
+            .expect("Should hit");
+        assert_eq!(Vec3::new(1.0, 0.0, -2.0), hit_record.p);
+        assert!(instance.hit(&ray.with_time(2.0), 0.0, 10.0).is_none());
+
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(-0.5, -0.5, -3.0),
+                Vec3::new(2.5, 0.5, -2.0)
+            )),
+            instance.bounding_box()
+        );
+    }
+
+    #[test]
+    fn instances_share_a_single_mesh() {
+        let mesh: Arc<dyn Hitable> = Arc::new(TriangleMesh::new(
+            vec![
+                Vec3::new(-0.5, -0.5, 0.0),
+                Vec3::new(0.5, -0.5, 0.0),
+                Vec3::new(0.0, 0.5, 0.0),
+            ],
+            vec![[0, 1, 2]],
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        ));
+        let mut list = HitableList::new();
+        for x in 0..100 {
+            for z in 1..=10 {
+                list.push(
+                    Instance::new(mesh.clone(), Mat4::identity())
+                        .translate(Vec3::new(x as f64, 0.0, -z as f64)),
+                );
+            }
+        }
+        let scene = Bvh::new(list);
+
+        assert_eq!(1001, Arc::strong_count(&mesh));
+        let ray = Ray::new(Vec3::new(42.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = scene.hit(&ray, 0.0, 100.0).expect("Should hit");
+        assert_eq!(Vec3::new(42.0, 0.0, -1.0), hit_record.p);
+    }
+
+    #[test]
+    fn moved_instances_are_hit_after_rebuilding_the_top_level() {
+        let sphere = unit_sphere();
+        let mut scene: InstancedScene = (0..10)
+            .map(|x| {
+                Instance::new(
+                    sphere.clone(),
+                    Mat4::translation(Vec3::new(3.0 * x as f64, 0.0, -5.0)),
+                )
+            })
+            .collect();
+        assert_eq!(10, scene.len());
+        let ray = Ray::new(Vec3::new(0.0, 10.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(9.0, scene.hit(&ray, 0.0, 100.0).expect("Should hit").t);
+
+        // the sphere at the origin is moved out of the way and another one under
+        // the ray, lifted up
+        scene.set_transform(0, Mat4::translation(Vec3::new(-10.0, 0.0, 0.0)));
+        scene.set_transform(5, Mat4::translation(Vec3::new(0.0, 2.0, -5.0)));
+        scene.rebuild();
+
+        assert_eq!(7.0, scene.hit(&ray, 0.0, 100.0).expect("Should hit").t);
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(-11.0, -1.0, -6.0),
+                Vec3::new(28.0, 3.0, 1.0)
+            )),
+            scene.bounding_box()
+        );
+        // the geometry itself is still shared by all of the instances
+        assert!(scene
+            .instances()
+            .iter()
+            .all(|instance| Arc::ptr_eq(instance.hitable(), &sphere)));
+    }
+
+    #[test]
+    fn changed_scenes_are_rebuilt_on_the_next_hit() {
+        let sphere = unit_sphere();
+        let mut scene = InstancedScene::new(vec![Instance::new(
+            sphere.clone(),
+            Mat4::translation(Vec3::new(0.0, 0.0, -5.0)),
+        )]);
+        let ray = Ray::new(Vec3::new(0.0, 10.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(9.0, scene.hit(&ray, 0.0, 100.0).expect("Should hit").t);
+
+        // neither change is followed by a rebuild
+        scene.set_transform(0, Mat4::translation(Vec3::new(-10.0, 0.0, 0.0)));
+        assert!(scene.hit(&ray, 0.0, 100.0).is_none());
+
+        let index = scene.push(Instance::new(
+            sphere,
+            Mat4::translation(Vec3::new(0.0, 2.0, -5.0)),
+        ));
+        assert_eq!(1, index);
+        assert_eq!(7.0, scene.hit(&ray, 0.0, 100.0).expect("Should hit").t);
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(-11.0, -1.0, -6.0),
+                Vec3::new(1.0, 3.0, 1.0)
+            )),
+            scene.bounding_box()
+        );
+    }
+
+    #[test]
+    fn instanced_scene_finds_the_same_hits_as_a_list() {
+        let sphere = unit_sphere();
+        let instances = || {
+            (0..50).map(|i| {
+                let i = i as f64;
+                Instance::new(sphere.clone(), Mat4::identity())
+                    .scale(Vec3::new(1.0 + i % 3.0, 1.0, 0.5))
+                    .rotate(Vec3::new(1.0, 2.0, 3.0), 7.0 * i)
+                    .translate(Vec3::new(i % 7.0 * 4.0, i % 5.0 * 4.0, -i))
+            })
+        };
+        let scene: InstancedScene = instances().collect();
+        let list: HitableList = instances().collect();
+
+        for x in 0..30 {
+            for y in 0..20 {
+                let ray = Ray::new(
+                    Vec3::new(x as f64, y as f64, 10.0),
+                    Vec3::new(0.1, -0.05, -1.0),
+                );
+
+                let expected = list.hit(&ray, 0.001, f64::MAX).map(|hr| hr.t);
+                let actual = scene.hit(&ray, 0.001, f64::MAX).map(|hr| hr.t);
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Instance transform must be invertible")]
+    fn it_panics_on_flattening_transforms() {
+        Instance::new(unit_sphere(), Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)));
+    }
+}
@@ -1,9 +1,27 @@
+pub use self::aabb::Aabb;
 pub use self::camera::Camera;
+pub use self::camera::Projection;
+pub use self::mat4::Mat4;
 pub use self::ray::Ray;
 pub use self::vec3::Vec3;
 
+mod aabb;
+pub mod bvh;
 mod camera;
+pub mod environment;
+pub mod film;
 pub mod hitable;
+pub mod image;
+pub mod import;
+pub mod instance;
+mod mat4;
+pub mod material;
+pub mod mesh;
+pub mod noise;
 pub mod object;
 mod ray;
+pub mod render;
+#[cfg(test)]
+mod testing;
+pub mod texture;
 mod vec3;
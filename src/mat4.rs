@@ -0,0 +1,410 @@
+use std::ops::Mul;
+
+use crate::aabb::Aabb;
+use crate::Vec3;
+
+/// Row-major 4x4 matrix of a transform in homogeneous coordinates, applied to
+/// column vectors such that `a * b` applies `b` first and then `a`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { m: rows }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Negative `factors` mirror along their axes.
+    pub fn scaling(factors: Vec3) -> Self {
+        Self::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Counter-clockwise rotation by `angle` (in degrees) around the `axis` when
+    /// it points towards the viewer.
+    pub fn rotation(axis: Vec3, angle: f64) -> Self {
+        let axis = Vec3::unit_vector(axis);
+        let (x, y, z) = (axis.x(), axis.y(), axis.z());
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let one_minus_cos = 1.0 - cos;
+
+        Self::new([
+            [
+                cos + x * x * one_minus_cos,
+                x * y * one_minus_cos - z * sin,
+                x * z * one_minus_cos + y * sin,
+                0.0,
+            ],
+            [
+                y * x * one_minus_cos + z * sin,
+                cos + y * y * one_minus_cos,
+                y * z * one_minus_cos - x * sin,
+                0.0,
+            ],
+            [
+                z * x * one_minus_cos - y * sin,
+                z * y * one_minus_cos + x * sin,
+                cos + z * z * one_minus_cos,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// Interpolates every element, which is exact for translations and scalings
+    /// and approximates rotations by small angles.
+    pub fn lerp(from: Self, to: Self, t: f64) -> Self {
+        let mut m = from.m;
+        for (values, to) in m.iter_mut().zip(&to.m) {
+            for (value, to) in values.iter_mut().zip(to) {
+                *value += t * (to - *value);
+            }
+        }
+
+        Self::new(m)
+    }
+
+    pub fn rows(&self) -> [[f64; 4]; 4] {
+        self.m
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut transposed = [[0.0; 4]; 4];
+        for (row, values) in self.m.iter().enumerate() {
+            for (column, value) in values.iter().enumerate() {
+                transposed[column][row] = *value;
+            }
+        }
+
+        Self::new(transposed)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let mut m = self.m;
+        let mut determinant = 1.0;
+
+        for column in 0..4 {
+            let pivot = pivot_row(&m, column);
+            if m[pivot][column] == 0.0 {
+                return 0.0;
+            }
+            if pivot != column {
+                m.swap(pivot, column);
+                determinant = -determinant;
+            }
+
+            determinant *= m[column][column];
+            let pivot_values = m[column];
+            for values in &mut m[column + 1..] {
+                let factor = values[column] / pivot_values[column];
+                for (value, pivot_value) in values.iter_mut().zip(&pivot_values).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+
+        determinant
+    }
+
+    /// Gauss-Jordan elimination, `None` for singular matrices (e.g. a zero scale)
+    /// which can't be undone.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut m = self.m;
+        let mut inverse = Self::identity().m;
+        // the row operations scale every column along with the matrix, so pivots
+        // are compared to their column to keep tiny scales invertible
+        let mut magnitudes = [0.0_f64; 4];
+        for row in &m {
+            for (magnitude, value) in magnitudes.iter_mut().zip(row) {
+                *magnitude = magnitude.max(value.abs());
+            }
+        }
+
+        for column in 0..4 {
+            let pivot = pivot_row(&m, column);
+            // NaN pivots are picked over any other value, and rejected here
+            if !m[pivot][column].is_finite() || m[pivot][column].abs() <= 1e-12 * magnitudes[column]
+            {
+                return None;
+            }
+            m.swap(pivot, column);
+            inverse.swap(pivot, column);
+
+            let divisor = m[column][column];
+            for k in 0..4 {
+                m[column][k] /= divisor;
+                inverse[column][k] /= divisor;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+
+                let factor = m[row][column];
+                for k in 0..4 {
+                    m[row][k] -= factor * m[column][k];
+                    inverse[row][k] -= factor * inverse[column][k];
+                }
+            }
+        }
+
+        Some(Self::new(inverse))
+    }
+
+    /// Transforms a position, which is affected by the translation.
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        let m = &self.m;
+        let transformed = self.transform_vector(point) + Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let w = m[3][0] * point.x() + m[3][1] * point.y() + m[3][2] * point.z() + m[3][3];
+
+        if w == 1.0 {
+            transformed
+        } else {
+            transformed / w
+        }
+    }
+
+    /// Transforms a direction, which is not affected by the translation.
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        let row = |row: &[f64; 4]| row[0] * vector.x() + row[1] * vector.y() + row[2] * vector.z();
+
+        Vec3::new(row(&self.m[0]), row(&self.m[1]), row(&self.m[2]))
+    }
+
+    /// Transforms a surface normal into a unit normal of the transformed surface.
+    ///
+    /// Normals are transformed by the inverse transpose, which is the cofactor
+    /// matrix divided by the determinant, so only the sign of the determinant
+    /// matters once normalized. That also works for flattening transforms which
+    /// have no inverse.
+    pub fn transform_normal(&self, normal: Vec3) -> Vec3 {
+        let column =
+            |column: usize| Vec3::new(self.m[0][column], self.m[1][column], self.m[2][column]);
+        let (x, y, z) = (column(0), column(1), column(2));
+
+        let cofactors = normal.x() * Vec3::cross(y, z)
+            + normal.y() * Vec3::cross(z, x)
+            + normal.z() * Vec3::cross(x, y);
+        let sign = if Vec3::dot(x, Vec3::cross(y, z)) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+
+        Vec3::unit_vector(sign * cofactors)
+    }
+
+    /// The box around the transformed corners of the `aabb`.
+    pub fn transform_aabb(&self, aabb: &Aabb) -> Aabb {
+        if aabb.is_empty() {
+            return *aabb;
+        }
+
+        Aabb::from_points((0..8).map(|corner| {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    aabb.min[axis]
+                } else {
+                    aabb.max[axis]
+                }
+            };
+
+            self.transform_point(Vec3::new(pick(0), pick(1), pick(2)))
+        }))
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let mut product = [[0.0; 4]; 4];
+        for (row, values) in product.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * rhs.m[k][column]).sum();
+            }
+        }
+
+        Self::new(product)
+    }
+}
+
+/// The row at or below the diagonal with the largest value in the `column`.
+fn pivot_row(m: &[[f64; 4]; 4], column: usize) -> usize {
+    (column..4)
+        .max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))
+        .unwrap_or(column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::assert_vec3_eq;
+
+    fn assert_mat4_eq(expected: Mat4, actual: Mat4) {
+        for (expected, actual) in expected.m.iter().flatten().zip(actual.m.iter().flatten()) {
+            assert!(
+                (expected - actual).abs() < 1e-10,
+                "{:?} != {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn it_transforms_points_and_vectors() {
+        let translation = Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+        let point = Vec3::new(1.0, 1.0, 1.0);
+
+        assert_eq!(Vec3::new(2.0, 3.0, 4.0), translation.transform_point(point));
+        assert_eq!(point, translation.transform_vector(point));
+        assert_eq!(point, Mat4::identity().transform_point(point));
+
+        let scaling = Mat4::scaling(Vec3::new(2.0, -1.0, 0.5));
+        assert_eq!(Vec3::new(2.0, -1.0, 0.5), scaling.transform_point(point));
+    }
+
+    #[test]
+    fn it_rotates_counter_clockwise() {
+        let rotation = Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), 90.0);
+
+        assert_vec3_eq(
+            Vec3::new(0.0, 1.0, 0.0),
+            rotation.transform_vector(Vec3::new(1.0, 0.0, 0.0)),
+        );
+        assert_vec3_eq(
+            Vec3::new(-1.0, 0.0, 0.0),
+            rotation.transform_vector(Vec3::new(0.0, 1.0, 0.0)),
+        );
+
+        let around_x = Mat4::rotation(Vec3::new(2.0, 0.0, 0.0), 90.0);
+        assert_vec3_eq(
+            Vec3::new(0.0, 0.0, 1.0),
+            around_x.transform_vector(Vec3::new(0.0, 1.0, 0.0)),
+        );
+    }
+
+    #[test]
+    fn it_applies_the_right_matrix_first() {
+        let translation = Mat4::translation(Vec3::new(1.0, 0.0, 0.0));
+        let scaling = Mat4::scaling(Vec3::new(2.0, 2.0, 2.0));
+        let point = Vec3::new(1.0, 1.0, 1.0);
+
+        assert_eq!(
+            Vec3::new(3.0, 2.0, 2.0),
+            (translation * scaling).transform_point(point)
+        );
+        assert_eq!(
+            Vec3::new(4.0, 2.0, 2.0),
+            (scaling * translation).transform_point(point)
+        );
+    }
+
+    #[test]
+    fn it_interpolates_between_matrices() {
+        let from = Mat4::translation(Vec3::new(0.0, 0.0, 0.0));
+        let to =
+            Mat4::translation(Vec3::new(2.0, 4.0, 0.0)) * Mat4::scaling(Vec3::new(3.0, 3.0, 3.0));
+
+        assert_eq!(from, Mat4::lerp(from, to, 0.0));
+        assert_eq!(to, Mat4::lerp(from, to, 1.0));
+        assert_eq!(
+            Vec3::new(3.0, 4.0, 2.0),
+            Mat4::lerp(from, to, 0.5).transform_point(Vec3::new(1.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn it_inverts_the_matrix() {
+        let transform = Mat4::translation(Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0)
+            * Mat4::scaling(Vec3::new(2.0, 3.0, -4.0));
+
+        let inverse = transform.inverse().expect("Should be invertible");
+        assert_mat4_eq(Mat4::identity(), transform * inverse);
+        assert_mat4_eq(Mat4::identity(), inverse * transform);
+        assert!((transform.determinant() + 24.0).abs() < 1e-10);
+
+        let flattening = Mat4::scaling(Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(None, flattening.inverse());
+        assert_eq!(0.0, flattening.determinant());
+
+        let tiny = Mat4::scaling(Vec3::new(1e-13, 1e-13, 1e-13))
+            * Mat4::rotation(Vec3::new(0.0, 1.0, 0.0), 30.0);
+        let inverse = tiny.inverse().expect("Should be invertible");
+        assert_mat4_eq(Mat4::identity(), tiny * inverse);
+
+        // a zero rotation axis makes the whole matrix NaN
+        let invalid = Mat4::rotation(Vec3::new(0.0, 0.0, 0.0), 30.0);
+        assert_eq!(None, invalid.inverse());
+    }
+
+    #[test]
+    fn it_keeps_normals_perpendicular_to_the_surface() {
+        // squashing a 45 degree slope makes it steeper
+        let scaling = Mat4::scaling(Vec3::new(1.0, 0.5, 1.0));
+        let tangent = scaling.transform_vector(Vec3::new(1.0, 1.0, 0.0));
+        let normal = scaling.transform_normal(Vec3::new(-1.0, 1.0, 0.0));
+
+        assert!(Vec3::dot(tangent, normal).abs() < 1e-10);
+        assert!((normal.length() - 1.0).abs() < 1e-10);
+
+        // mirroring keeps the normal on the outside
+        let mirror = Mat4::scaling(Vec3::new(-1.0, 1.0, 1.0));
+        assert_vec3_eq(
+            Vec3::new(-1.0, 0.0, 0.0),
+            mirror.transform_normal(Vec3::new(1.0, 0.0, 0.0)),
+        );
+    }
+
+    #[test]
+    fn it_transforms_bounding_boxes() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0));
+        let transform = Mat4::translation(Vec3::new(0.0, 0.0, 5.0))
+            * Mat4::rotation(Vec3::new(0.0, 0.0, 1.0), 45.0);
+
+        let transformed = transform.transform_aabb(&aabb);
+
+        let half_diagonal = 2.0_f64.sqrt();
+        assert_vec3_eq(
+            Vec3::new(-half_diagonal, -half_diagonal, 4.0),
+            transformed.min,
+        );
+        assert_vec3_eq(
+            Vec3::new(half_diagonal, half_diagonal, 6.0),
+            transformed.max,
+        );
+        assert!(transform.transform_aabb(&Aabb::empty()).is_empty());
+    }
+}
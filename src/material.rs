@@ -0,0 +1,431 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use crate::hitable::HitRecord;
+use crate::texture::{SolidColor, Texture};
+use crate::Ray;
+use crate::Vec3;
+
+/// The result of a ray bouncing off a surface.
+pub struct Scatter {
+    /// How much of each color channel survives the bounce.
+    pub attenuation: Vec3,
+    /// The ray leaving the surface.
+    pub ray: Ray,
+    /// The probability density of picking the direction of the `ray`, `None` for
+    /// specular bounces which only ever pick a single direction.
+    pub pdf: Option<f64>,
+}
+
+impl Scatter {
+    /// Creates a specular bounce.
+    pub fn new(attenuation: Vec3, ray: Ray) -> Self {
+        Self {
+            attenuation,
+            ray,
+            pdf: None,
+        }
+    }
+
+    /// Creates a bounce in a randomly picked direction with the given probability density.
+    pub fn with_pdf(attenuation: Vec3, ray: Ray, pdf: f64) -> Self {
+        Self {
+            attenuation,
+            ray,
+            pdf: Some(pdf),
+        }
+    }
+}
+
+/// Describes how light interacts with the surface of an object.
+pub trait Material: Send + Sync {
+    /// Returns the scattered ray and its attenuation, or `None` if the
+    /// incoming ray is absorbed.
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut dyn RngCore) -> Option<Scatter>;
+
+    /// The light given off by the surface, black unless the material is a light source.
+    fn emitted(&self, _ray: &Ray, _hit_record: &HitRecord) -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Returns how much of the light arriving from `direction` is scattered back
+    /// along the `ray` (the BSDF times the cosine term) and the probability density of
+    /// `scatter` picking that `direction`.
+    ///
+    /// This allows light sources to be sampled directly. Materials which only scatter
+    /// in specular directions return `None`.
+    fn eval(&self, _ray: &Ray, _hit_record: &HitRecord, _direction: Vec3) -> Option<(Vec3, f64)> {
+        None
+    }
+}
+
+/// Ideal diffuse surface which bounces light in a random direction.
+pub struct Lambertian {
+    pub albedo: Arc<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Vec3) -> Self {
+        Self::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// Looks the `albedo` up at the surface coordinates of the hit point.
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut dyn RngCore) -> Option<Scatter> {
+        // points on the unit sphere around the normal are cosine distributed
+        let mut direction = hit_record.normal + Vec3::unit_vector(random_in_unit_sphere(rng));
+        if direction.squared_length() < 1e-12 {
+            direction = hit_record.normal;
+        }
+        let cosine = Vec3::dot(Vec3::unit_vector(direction), hit_record.normal);
+
+        Some(Scatter::with_pdf(
+            albedo(&*self.albedo, hit_record),
+            Ray::new(hit_record.p, direction).with_time(ray.time),
+            cosine / PI,
+        ))
+    }
+
+    fn eval(&self, _ray: &Ray, hit_record: &HitRecord, direction: Vec3) -> Option<(Vec3, f64)> {
+        let cosine = Vec3::dot(Vec3::unit_vector(direction), hit_record.normal).max(0.0);
+
+        Some((albedo(&*self.albedo, hit_record) * cosine / PI, cosine / PI))
+    }
+}
+
+/// Reflective surface, the bigger the `fuzz` the blurrier the reflection.
+pub struct Metal {
+    pub albedo: Arc<dyn Texture>,
+    pub fuzz: f64,
+}
+
+impl Metal {
+    /// The `fuzz` is clamped to `1.0`.
+    pub fn new(albedo: Vec3, fuzz: f64) -> Self {
+        Self::textured(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    /// Looks the `albedo` up at the surface coordinates of the hit point, the `fuzz`
+    /// is clamped to `1.0`.
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
+        Self {
+            albedo,
+            fuzz: fuzz.min(1.0),
+        }
+    }
+}
+
+impl Material for Metal {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let reflected = Vec3::reflect(Vec3::unit_vector(ray.direction), hit_record.normal);
+        let scattered = Ray::new(
+            hit_record.p,
+            reflected + self.fuzz * random_in_unit_sphere(rng),
+        )
+        .with_time(ray.time);
+
+        if Vec3::dot(scattered.direction, hit_record.normal) > 0.0 {
+            Some(Scatter::new(albedo(&*self.albedo, hit_record), scattered))
+        } else {
+            None
+        }
+    }
+}
+
+/// Clear material like glass or water which both reflects and refracts light.
+pub struct Dielectric {
+    /// The index of refraction, e.g. `1.5` for glass.
+    pub ref_idx: f64,
+}
+
+impl Dielectric {
+    pub fn new(ref_idx: f64) -> Self {
+        Self { ref_idx }
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let attenuation = Vec3::new(1.0, 1.0, 1.0);
+        let cosine = Vec3::dot(ray.direction, hit_record.normal) / ray.direction.length();
+
+        // the normals point outwards unless they were flipped towards the ray, so
+        // a positive cosine on the front face means we are leaving the object
+        let leaving = (cosine > 0.0) == hit_record.front_face;
+        let facing_normal = if cosine > 0.0 {
+            -hit_record.normal
+        } else {
+            hit_record.normal
+        };
+        let cosine = cosine.abs();
+        let (ni_over_nt, cosine) = if leaving {
+            let cosine_inside = 1.0 - self.ref_idx.powi(2) * (1.0 - cosine.powi(2));
+
+            (self.ref_idx, cosine_inside.max(0.0).sqrt())
+        } else {
+            (1.0 / self.ref_idx, cosine)
+        };
+
+        let direction = match Vec3::refract(ray.direction, facing_normal, ni_over_nt) {
+            Some(refracted) if rng.gen::<f64>() >= schlick(cosine, self.ref_idx) => refracted,
+            _ => Vec3::reflect(ray.direction, hit_record.normal),
+        };
+
+        Some(Scatter::new(
+            attenuation,
+            Ray::new(hit_record.p, direction).with_time(ray.time),
+        ))
+    }
+}
+
+/// Light source which emits the same light in every direction and does not
+/// reflect anything.
+pub struct DiffuseLight {
+    pub emit: Vec3,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _hit_record: &HitRecord,
+        _rng: &mut dyn RngCore,
+    ) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, _hit_record: &HitRecord) -> Vec3 {
+        self.emit
+    }
+}
+
+/// The color of the `texture` at the hit point.
+fn albedo(texture: &dyn Texture, hit_record: &HitRecord) -> Vec3 {
+    texture.value(hit_record.u, hit_record.v, hit_record.p)
+}
+
+/// Schlick's approximation of the Fresnel reflectance.
+fn schlick(cosine: f64, ref_idx: f64) -> f64 {
+    let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
+
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+pub(crate) fn random_in_unit_sphere(rng: &mut dyn RngCore) -> Vec3 {
+    let mut p: Vec3;
+    loop {
+        let random_vec3 = Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>());
+        p = 2.0 * random_vec3 - Vec3::new(1.0, 1.0, 1.0);
+        if p.squared_length() < 1.0 {
+            break;
+        }
+    }
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::texture::Checker;
+
+    #[test]
+    fn it_picks_random_points_inside_the_unit_sphere() {
+        let mut rng = thread_rng();
+
+        for _ in 0..100 {
+            assert!(random_in_unit_sphere(&mut rng).squared_length() < 1.0);
+        }
+    }
+
+    #[test]
+    fn lambertian_scatters_from_the_hit_point_with_its_albedo() {
+        let mut rng = thread_rng();
+        let material = Lambertian::new(Vec3::new(0.1, 0.2, 0.3));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = HitRecord::new(
+            1.0,
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            &material,
+        );
+
+        let scatter = material
+            .scatter(&ray, &hit_record, &mut rng)
+            .expect("Lambertian always scatters");
+
+        assert_eq!(Vec3::new(0.1, 0.2, 0.3), scatter.attenuation);
+        assert_eq!(Vec3::new(0.0, 0.0, -1.0), scatter.ray.origin);
+        // the scattered ray always leaves on the side of the normal
+        assert!(Vec3::dot(scatter.ray.direction, hit_record.normal) > 0.0);
+
+        let (value, pdf) = material
+            .eval(&ray, &hit_record, scatter.ray.direction)
+            .expect("Lambertian can be lit directly");
+        assert!((scatter.pdf.expect("Diffuse bounce") - pdf).abs() < 1e-10);
+        // the attenuation is the BSDF times the cosine divided by the pdf
+        assert!((value / pdf - scatter.attenuation).length() < 1e-10);
+    }
+
+    #[test]
+    fn dielectric_knows_it_is_leaving_through_a_back_face() {
+        let mut rng = thread_rng();
+        let material = Dielectric::new(1.5);
+        // beyond the critical angle when leaving the glass, so all light is reflected
+        let ray = Ray::new(Vec3::new(-2.0, 0.0, -1.0), Vec3::new(2.0, 0.0, 1.0));
+        // the back face of a triangle, with the normal flipped towards the ray
+        let hit_record = HitRecord::new(
+            1.0,
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            &material,
+        )
+        .with_front_face(false);
+
+        for _ in 0..100 {
+            let scatter = material
+                .scatter(&ray, &hit_record, &mut rng)
+                .expect("Dielectric always scatters");
+
+            assert_eq!(Vec3::new(2.0, 0.0, -1.0), scatter.ray.direction);
+        }
+    }
+
+    #[test]
+    fn lambertian_does_not_reflect_light_from_below_the_surface() {
+        let material = Lambertian::new(Vec3::new(0.5, 0.5, 0.5));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = HitRecord::new(
+            1.0,
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            &material,
+        );
+
+        assert_eq!(
+            Some((Vec3::new(0.0, 0.0, 0.0), 0.0)),
+            material.eval(&ray, &hit_record, Vec3::new(0.0, 0.0, -1.0))
+        );
+        assert_eq!(
+            Some((Vec3::new(0.5, 0.5, 0.5) / PI, 1.0 / PI)),
+            material.eval(&ray, &hit_record, Vec3::new(0.0, 0.0, 2.0))
+        );
+    }
+
+    #[test]
+    fn lambertian_looks_up_its_albedo_at_the_hit_point() {
+        let mut rng = thread_rng();
+        let material = Lambertian::textured(Arc::new(Checker::new(
+            Arc::new(SolidColor::new(Vec3::new(1.0, 1.0, 1.0))),
+            Arc::new(SolidColor::new(Vec3::new(0.0, 0.0, 0.0))),
+            1.0,
+        )));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+
+        let even = HitRecord::new(1.0, Vec3::new(0.5, 0.5, -1.5), normal, &material);
+        let scatter = material
+            .scatter(&ray, &even, &mut rng)
+            .expect("Should scatter");
+        assert_eq!(Vec3::new(1.0, 1.0, 1.0), scatter.attenuation);
+
+        let odd = HitRecord::new(1.0, Vec3::new(0.5, 0.5, -0.5), normal, &material);
+        let scatter = material
+            .scatter(&ray, &odd, &mut rng)
+            .expect("Should scatter");
+        assert_eq!(Vec3::new(0.0, 0.0, 0.0), scatter.attenuation);
+    }
+
+    #[test]
+    fn metal_clamps_fuzz_and_mirrors_the_ray() {
+        let mut rng = thread_rng();
+        assert_eq!(1.0, Metal::new(Vec3::new(1.0, 1.0, 1.0), 2.0).fuzz);
+
+        let material = Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.0);
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let hit_record = HitRecord::new(
+            1.0,
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &material,
+        );
+
+        let scatter = material
+            .scatter(&ray, &hit_record, &mut rng)
+            .expect("Should reflect");
+
+        assert_eq!(Vec3::new(0.8, 0.6, 0.2), scatter.attenuation);
+        assert_eq!(
+            Vec3::unit_vector(Vec3::new(1.0, 1.0, 0.0)),
+            scatter.ray.direction
+        );
+        assert_eq!(None, scatter.pdf);
+        assert!(material
+            .eval(&ray, &hit_record, scatter.ray.direction)
+            .is_none());
+    }
+
+    #[test]
+    fn dielectric_reflects_on_total_internal_reflection() {
+        let mut rng = thread_rng();
+        let material = Dielectric::new(1.5);
+        // leaving the glass at a grazing angle
+        let ray = Ray::new(Vec3::new(-1.0, -0.1, 0.0), Vec3::new(1.0, 0.1, 0.0));
+        let hit_record = HitRecord::new(
+            1.0,
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &material,
+        );
+
+        for _ in 0..10 {
+            let scatter = material
+                .scatter(&ray, &hit_record, &mut rng)
+                .expect("Dielectric always scatters");
+
+            assert_eq!(Vec3::new(1.0, 1.0, 1.0), scatter.attenuation);
+            assert_eq!(Vec3::new(1.0, -0.1, 0.0), scatter.ray.direction);
+        }
+    }
+
+    #[test]
+    fn only_diffuse_light_emits_light() {
+        let mut rng = thread_rng();
+        let light = DiffuseLight::new(Vec3::new(4.0, 4.0, 4.0));
+        let lambertian = Lambertian::new(Vec3::new(0.5, 0.5, 0.5));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = HitRecord::new(
+            1.0,
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            &light,
+        );
+
+        assert!(light.scatter(&ray, &hit_record, &mut rng).is_none());
+        assert_eq!(Vec3::new(4.0, 4.0, 4.0), light.emitted(&ray, &hit_record));
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            lambertian.emitted(&ray, &hit_record)
+        );
+    }
+
+    #[test]
+    fn schlick_reflects_everything_at_grazing_angles() {
+        assert_eq!(1.0, schlick(0.0, 1.5));
+        assert!((0.04 - schlick(1.0, 1.5)).abs() < 1e-10);
+    }
+}
@@ -0,0 +1,330 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::aabb::Aabb;
+use crate::bvh::Tree;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::object::{
+    face_normal, interpolate, interpolate_uv, intersect_triangle, is_front_face, DEFAULT_TEXCOORDS,
+};
+use crate::Ray;
+use crate::Vec3;
+
+/// Triangles sharing the vertices in a single buffer, where every triangle is
+/// three indices into the vertex buffer.
+///
+/// The triangles are put into a BVH on the first hit, which is why the buffers
+/// can't be changed after creating the mesh.
+pub struct TriangleMesh {
+    positions: Vec<Vec3>,
+    /// Either empty or one normal per position.
+    normals: Vec<Vec3>,
+    /// Either empty or one texture coordinate per position.
+    texcoords: Vec<[f64; 2]>,
+    indices: Vec<[usize; 3]>,
+    pub material: Arc<dyn Material>,
+    bvh: OnceLock<Tree>,
+}
+
+impl TriangleMesh {
+    /// # Panics
+    ///
+    /// If any of the `indices` points outside of the `positions`.
+    pub fn new(
+        positions: Vec<Vec3>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        assert!(
+            indices
+                .iter()
+                .flatten()
+                .all(|&index| index < positions.len()),
+            "Vertex index out of bounds"
+        );
+
+        Self {
+            positions,
+            normals: vec![],
+            texcoords: vec![],
+            indices,
+            material,
+            bvh: OnceLock::new(),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If there isn't exactly one normal per position.
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(
+            self.positions.len(),
+            normals.len(),
+            "Every vertex needs a normal"
+        );
+
+        self.normals = normals;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// If there isn't exactly one texture coordinate per position.
+    pub fn with_texcoords(mut self, texcoords: Vec<[f64; 2]>) -> Self {
+        assert_eq!(
+            self.positions.len(),
+            texcoords.len(),
+            "Every vertex needs a texture coordinate"
+        );
+
+        self.texcoords = texcoords;
+        self
+    }
+
+    pub fn positions(&self) -> &[Vec3] {
+        &self.positions
+    }
+
+    /// Either empty or one normal per position.
+    pub fn normals(&self) -> &[Vec3] {
+        &self.normals
+    }
+
+    /// Either empty or one texture coordinate per position.
+    pub fn texcoords(&self) -> &[[f64; 2]] {
+        &self.texcoords
+    }
+
+    pub fn indices(&self) -> &[[usize; 3]] {
+        &self.indices
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// The positions of the three vertices of the triangle at `index`.
+    pub fn vertices(&self, index: usize) -> [Vec3; 3] {
+        let [a, b, c] = self.indices[index];
+
+        [self.positions[a], self.positions[b], self.positions[c]]
+    }
+
+    fn bvh(&self) -> &Tree {
+        self.bvh.get_or_init(|| {
+            let bounds: Vec<Aabb> = (0..self.len())
+                .map(|index| Aabb::from_points(self.vertices(index).iter().copied()))
+                .collect();
+
+            Tree::new(&bounds)
+        })
+    }
+
+    fn hit_triangle(
+        &self,
+        index: usize,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord<'_>> {
+        let vertices = self.vertices(index);
+        let (t, barycentric) = intersect_triangle(ray, vertices, t_min, t_max)?;
+
+        let [a, b, c] = self.indices[index];
+
+        let normal = if self.normals.is_empty() {
+            face_normal(vertices)
+        } else {
+            interpolate(
+                [self.normals[a], self.normals[b], self.normals[c]],
+                barycentric,
+            )
+        };
+        let texcoords = if self.texcoords.is_empty() {
+            DEFAULT_TEXCOORDS
+        } else {
+            [self.texcoords[a], self.texcoords[b], self.texcoords[c]]
+        };
+        let (u, v) = interpolate_uv(texcoords, barycentric);
+
+        Some(
+            HitRecord::new(
+                t,
+                ray.point_at_parameter(t),
+                Vec3::unit_vector(normal),
+                &*self.material,
+            )
+            .with_barycentric(barycentric)
+            .with_uv(u, v)
+            .with_front_face(is_front_face(ray, vertices)),
+        )
+    }
+}
+
+impl Hitable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bvh().hit(ray, t_min, t_max, |index, closest_so_far| {
+            self.hit_triangle(index, ray, t_min, closest_so_far)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bvh().bounding_box())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    /// Two triangles making up a square facing +z.
+    fn square() -> TriangleMesh {
+        TriangleMesh::new(
+            vec![
+                Vec3::new(-1.0, -1.0, -1.0),
+                Vec3::new(1.0, -1.0, -1.0),
+                Vec3::new(1.0, 1.0, -1.0),
+                Vec3::new(-1.0, 1.0, -1.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn it_hits_the_triangles_of_the_mesh() {
+        let mesh = square();
+        assert_eq!(2, mesh.len());
+
+        let lower_right = Ray::new(Vec3::new(0.5, -0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = mesh.hit(&lower_right, 0.0, 10.0).expect("Should hit");
+        assert_eq!(Vec3::new(0.5, -0.5, -1.0), hit_record.p);
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+        assert_eq!(Some([0.25, 0.5, 0.25]), hit_record.barycentric);
+
+        let upper_left = Ray::new(Vec3::new(-0.5, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = mesh.hit(&upper_left, 0.0, 10.0).expect("Should hit");
+        assert_eq!(Vec3::new(-0.5, 0.5, -1.0), hit_record.p);
+        assert_eq!(Some([0.25, 0.25, 0.5]), hit_record.barycentric);
+
+        let outside = Ray::new(Vec3::new(1.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&outside, 0.0, 10.0).is_none());
+    }
+
+    #[test]
+    fn it_returns_the_closest_triangle() {
+        let square = square();
+        let mut positions = square.positions().to_vec();
+        positions.extend(vec![
+            Vec3::new(-1.0, -1.0, -0.5),
+            Vec3::new(1.0, -1.0, -0.5),
+            Vec3::new(0.0, 1.0, -0.5),
+        ]);
+        let mut indices = square.indices().to_vec();
+        indices.push([4, 5, 6]);
+        let mesh = TriangleMesh::new(positions, indices, square.material);
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!(0.5, hit_record.t);
+    }
+
+    #[test]
+    fn it_interpolates_the_vertex_normals() {
+        let mesh = square().with_normals(vec![
+            Vec3::new(-1.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(-1.0, 0.0, 1.0),
+        ]);
+        let ray = Ray::new(Vec3::new(0.0, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let hit_record = mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+    }
+
+    #[test]
+    fn it_flips_the_normals_of_triangles_hit_from_behind() {
+        let mesh = square().with_normals(vec![Vec3::new(-1.0, 0.0, 1.0); 4]);
+        let ray = Ray::new(Vec3::new(0.0, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0));
+
+        let hit_record = mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert!(!hit_record.front_face);
+        assert_eq!(
+            Vec3::unit_vector(Vec3::new(1.0, 0.0, -1.0)),
+            hit_record.normal
+        );
+    }
+
+    #[test]
+    fn it_interpolates_the_texture_coordinates() {
+        let mesh = square().with_texcoords(vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        let ray = Ray::new(Vec3::new(0.5, -0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let hit_record = mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!((0.75, 0.25), (hit_record.u, hit_record.v));
+
+        // without texture coordinates the barycentric coordinates are used
+        let mesh = square();
+        let hit_record = mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!((0.5, 0.25), (hit_record.u, hit_record.v));
+    }
+
+    #[test]
+    fn it_hits_large_meshes() {
+        // a 100 by 100 grid of squares facing +z
+        let size = 100;
+        let positions = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| Vec3::new(x as f64, y as f64, -1.0)))
+            .collect();
+        let indices = (0..size)
+            .flat_map(|y| (0..size).map(move |x| y * (size + 1) + x))
+            .flat_map(|corner| {
+                vec![
+                    [corner, corner + 1, corner + size + 2],
+                    [corner, corner + size + 2, corner + size + 1],
+                ]
+            })
+            .collect();
+        let mesh = TriangleMesh::new(
+            positions,
+            indices,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        );
+
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(0.0, 0.0, -1.0),
+                Vec3::new(100.0, 100.0, -1.0)
+            )),
+            mesh.bounding_box()
+        );
+
+        let ray = Ray::new(Vec3::new(42.25, 17.75, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = mesh.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!(Vec3::new(42.25, 17.75, -1.0), hit_record.p);
+
+        let outside = Ray::new(Vec3::new(100.5, 50.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&outside, 0.0, 10.0).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Vertex index out of bounds")]
+    fn it_panics_on_invalid_indices() {
+        TriangleMesh::new(
+            vec![Vec3::new(0.0, 0.0, 0.0)],
+            vec![[0, 0, 1]],
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        );
+    }
+}
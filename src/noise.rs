@@ -0,0 +1,250 @@
+//! Coherent noise for procedural textures.
+//!
+//! Both generators hash the integer lattice cell of a point through three random
+//! permutations, so they repeat every 256 units along each axis.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use crate::Vec3;
+
+const POINT_COUNT: usize = 256;
+
+/// Improved Perlin gradient noise, smooth and zero at every point of the integer
+/// lattice.
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(rng: &mut dyn RngCore) -> Self {
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let gradient = 2.0 * random_vec3(rng) - 1.0;
+                let squared_length = gradient.squared_length();
+                if squared_length > 1e-6 && squared_length <= 1.0 {
+                    break Vec3::unit_vector(gradient);
+                }
+            })
+            .collect();
+
+        Self {
+            gradients,
+            permutations: permutations(rng),
+        }
+    }
+
+    /// The noise at `p`, within `[-1, 1]`.
+    pub fn noise(&self, p: Vec3) -> f64 {
+        let cell = [p.x().floor(), p.y().floor(), p.z().floor()];
+        let fraction = p - Vec3::new(cell[0], cell[1], cell[2]);
+        let cell = cell.map(|coordinate| coordinate as i64);
+        let weights = [fraction.x(), fraction.y(), fraction.z()].map(fade);
+
+        let mut accumulated = 0.0;
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let gradient = self.gradients[hash(
+                &self.permutations,
+                [
+                    cell[0] + offset[0] as i64,
+                    cell[1] + offset[1] as i64,
+                    cell[2] + offset[2] as i64,
+                ],
+            )];
+            let towards =
+                fraction - Vec3::new(offset[0] as f64, offset[1] as f64, offset[2] as f64);
+
+            let weight = (0..3)
+                .map(|axis| {
+                    if offset[axis] == 1 {
+                        weights[axis]
+                    } else {
+                        1.0 - weights[axis]
+                    }
+                })
+                .product::<f64>();
+
+            accumulated += weight * Vec3::dot(gradient, towards);
+        }
+
+        accumulated.clamp(-1.0, 1.0)
+    }
+
+    /// Sums the absolute noise of `depth` octaves, each at twice the frequency and
+    /// half the weight of the previous one, which gives the creases of
+    /// marble-like patterns.
+    pub fn turbulence(&self, p: Vec3, depth: usize) -> f64 {
+        let mut accumulated = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accumulated += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p *= 2.0;
+        }
+
+        accumulated
+    }
+
+    /// Fractional Brownian motion, the sum of `octaves` of noise where every octave
+    /// multiplies the frequency by the `lacunarity` and the amplitude by the `gain`.
+    pub fn fbm(&self, p: Vec3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let mut accumulated = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+
+        for _ in 0..octaves {
+            accumulated += amplitude * self.noise(frequency * p);
+            frequency *= lacunarity;
+            amplitude *= gain;
+        }
+
+        accumulated
+    }
+}
+
+/// Worley cellular noise, the distance to the nearest of the feature points
+/// scattered one per cell of the integer lattice.
+pub struct Worley {
+    points: Vec<Vec3>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Worley {
+    pub fn new(rng: &mut dyn RngCore) -> Self {
+        Self {
+            points: (0..POINT_COUNT).map(|_| random_vec3(rng)).collect(),
+            permutations: permutations(rng),
+        }
+    }
+
+    /// The distance from `p` to the nearest feature point, at most `sqrt(3)`.
+    pub fn distance(&self, p: Vec3) -> f64 {
+        let cell =
+            [p.x().floor(), p.y().floor(), p.z().floor()].map(|coordinate| coordinate as i64);
+
+        // searching only the cell of `p` and its neighbours is the usual
+        // approximation, the nearest point is rarely further away, which leaves
+        // a few small discontinuities
+        let mut nearest = f64::INFINITY;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbour = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    let point = Vec3::new(
+                        neighbour[0] as f64,
+                        neighbour[1] as f64,
+                        neighbour[2] as f64,
+                    ) + self.points[hash(&self.permutations, neighbour)];
+
+                    nearest = nearest.min((point - p).squared_length());
+                }
+            }
+        }
+
+        nearest.sqrt()
+    }
+}
+
+/// Quintic smoothstep, which keeps the second derivative of the noise continuous
+/// across the cells.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn random_vec3(rng: &mut dyn RngCore) -> Vec3 {
+    Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>())
+}
+
+fn permutations(rng: &mut dyn RngCore) -> [Vec<usize>; 3] {
+    [(); 3].map(|_| {
+        let mut permutation: Vec<usize> = (0..POINT_COUNT).collect();
+        permutation.shuffle(rng);
+        permutation
+    })
+}
+
+fn hash(permutations: &[Vec<usize>; 3], cell: [i64; 3]) -> usize {
+    let index =
+        |axis: usize| permutations[axis][cell[axis].rem_euclid(POINT_COUNT as i64) as usize];
+
+    index(0) ^ index(1) ^ index(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_pcg::Pcg32;
+
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Vec3> {
+        (0..1000).map(|index| {
+            let index = f64::from(index);
+            Vec3::new(index * 0.137 - 50.0, index * 0.071, -index * 0.193)
+        })
+    }
+
+    #[test]
+    fn perlin_noise_is_zero_on_the_lattice() {
+        let perlin = Perlin::new(&mut Pcg32::new(1, 2));
+
+        for x in -3..3 {
+            for y in -3..3 {
+                let p = Vec3::new(f64::from(x), f64::from(y), 7.0);
+                assert!(perlin.noise(p).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn perlin_noise_is_smooth_and_bounded() {
+        let perlin = Perlin::new(&mut Pcg32::new(1, 2));
+        let step = Vec3::new(1e-4, 1e-4, 1e-4);
+
+        let mut varies = false;
+        for p in sample_points() {
+            let noise = perlin.noise(p);
+            assert!((-1.0..=1.0).contains(&noise));
+            assert!((noise - perlin.noise(p + step)).abs() < 1e-3);
+            varies |= noise.abs() > 0.1;
+        }
+        assert!(varies);
+
+        // the same seed gives the same noise
+        let again = Perlin::new(&mut Pcg32::new(1, 2));
+        let p = Vec3::new(0.3, 1.7, -2.2);
+        assert_eq!(perlin.noise(p), again.noise(p));
+    }
+
+    #[test]
+    fn it_sums_the_octaves() {
+        let perlin = Perlin::new(&mut Pcg32::new(3, 4));
+        let p = Vec3::new(0.3, 1.7, -2.2);
+
+        assert_eq!(perlin.noise(p).abs(), perlin.turbulence(p, 1));
+        assert_eq!(perlin.noise(p), perlin.fbm(p, 1, 2.0, 0.5));
+        assert_eq!(
+            perlin.noise(p) + 0.5 * perlin.noise(2.0 * p),
+            perlin.fbm(p, 2, 2.0, 0.5)
+        );
+        assert!(perlin.turbulence(p, 7) >= 0.0);
+        assert_eq!(0.0, perlin.fbm(p, 0, 2.0, 0.5));
+    }
+
+    #[test]
+    fn worley_noise_is_the_distance_to_the_nearest_feature_point() {
+        let worley = Worley::new(&mut Pcg32::new(5, 6));
+
+        for p in sample_points() {
+            let distance = worley.distance(p);
+            assert!((0.0..=3f64.sqrt()).contains(&distance));
+        }
+
+        let cell = [2, -3, 4];
+        let point = Vec3::new(2.0, -3.0, 4.0) + worley.points[hash(&worley.permutations, cell)];
+        assert!(worley.distance(point) < 1e-12);
+    }
+}
@@ -1,54 +1,626 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
 use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
 use crate::Ray;
 use crate::Vec3;
 
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f64,
+    pub material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Vec3, radius: f64) -> Self {
-        Self { center, radius }
+    pub fn new(center: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+        }
     }
 }
 
 impl Hitable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let origin_center = ray.origin - self.center;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(sphere_bounds(self.center, self.radius))
+    }
+}
+
+/// A sphere moving in a straight line from `center0` at `time0` to `center1` at
+/// `time1`, resting at those ends before and after.
+pub struct MovingSphere {
+    pub center0: Vec3,
+    pub center1: Vec3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Vec3,
+        center1: Vec3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Vec3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+
+        let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + t * (self.center1 - self.center0)
+    }
+}
+
+impl Hitable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time);
+
+        hit_sphere(center, self.radius, &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let start = sphere_bounds(self.center0, self.radius);
+        let end = sphere_bounds(self.center1, self.radius);
+
+        Some(start.union(&end))
+    }
+}
+
+fn hit_sphere<'a>(
+    center: Vec3,
+    radius: f64,
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    let origin_center = ray.origin - center;
+
+    let a: f64 = Vec3::dot(ray.direction, ray.direction);
+    let b: f64 = Vec3::dot(origin_center, ray.direction);
+    let c: f64 = Vec3::dot(origin_center, origin_center) - radius.powi(2);
+    let discriminant = b.powi(2) - a * c;
+
+    if discriminant > 0.0 {
+        let discr_sqrt = discriminant.sqrt();
+        let temp: f64 = (-b - discr_sqrt) / a;
+
+        if temp < t_max && temp > t_min {
+            let p = ray.point_at_parameter(temp);
+            let normal = (p - center) / radius;
+            let (u, v) = sphere_uv((p - center) / radius.abs());
+
+            return Some(HitRecord::new(temp, p, normal, material).with_uv(u, v));
+        }
+
+        let temp: f64 = (-b + discr_sqrt) / a;
+
+        if temp < t_max && temp > t_min {
+            let p = ray.point_at_parameter(temp);
+            let normal = (p - center) / radius;
+            let (u, v) = sphere_uv((p - center) / radius.abs());
+
+            return Some(HitRecord::new(temp, p, normal, material).with_uv(u, v));
+        }
+    }
+
+    None
+}
+
+fn sphere_bounds(center: Vec3, radius: f64) -> Aabb {
+    // negative radii make hollow spheres
+    let radius = radius.abs();
+    let extent = Vec3::new(radius, radius, radius);
+
+    Aabb::new(center - extent, center + extent)
+}
+
+/// Maps a `direction` from the center of the unit sphere to its surface
+/// coordinates, `u` going around the y axis starting at -x and `v` going from the
+/// bottom to the top.
+fn sphere_uv(direction: Vec3) -> (f64, f64) {
+    let theta = (-direction.y()).clamp(-1.0, 1.0).acos();
+    let phi = (-direction.z()).atan2(direction.x()) + PI;
 
-        let a: f64 = Vec3::dot(ray.direction, ray.direction);
-        let b: f64 = Vec3::dot(origin_center, ray.direction);
-        let c: f64 = Vec3::dot(origin_center, origin_center) - self.radius.powi(2);
-        let discriminant = b.powi(2) - a * c;
+    (phi / (2.0 * PI), theta / PI)
+}
 
-        if discriminant > 0.0 {
-            let discr_sqrt = discriminant.sqrt();
-            let temp: f64 = (-b - discr_sqrt) / a;
+/// A flat parallelogram spanned by the `u` and `v` edges starting at `corner`,
+/// e.g. the walls and area lights of a Cornell box.
+pub struct Quad {
+    pub corner: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material>,
+    normal: Vec3,
+    w: Vec3,
+}
 
-            if temp < t_max && temp > t_min {
-                let p = ray.point_at_parameter(temp);
-                let normal = (p - self.center) / self.radius;
+impl Quad {
+    pub fn new(corner: Vec3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = Vec3::cross(u, v);
 
-                return Some(HitRecord::new(temp, p, normal));
-            }
+        Self {
+            corner,
+            u,
+            v,
+            material,
+            normal: Vec3::unit_vector(n),
+            w: n / Vec3::dot(n, n),
+        }
+    }
+}
 
-            let temp: f64 = (-b + discr_sqrt) / a;
+impl Hitable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denominator = Vec3::dot(self.normal, ray.direction);
 
-            if temp < t_max && temp > t_min {
-                let p = ray.point_at_parameter(temp);
-                let normal = (p - self.center) / self.radius;
+        // the ray is parallel to the plane
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
 
-                return Some(HitRecord::new(temp, p, normal));
-            }
+        let t = Vec3::dot(self.normal, self.corner - ray.origin) / denominator;
+        if t >= t_max || t <= t_min {
+            return None;
         }
 
-        None
+        let p = ray.point_at_parameter(t);
+        let planar = p - self.corner;
+        let alpha = Vec3::dot(self.w, Vec3::cross(planar, self.v));
+        let beta = Vec3::dot(self.w, Vec3::cross(self.u, planar));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        // quads are two-sided, so the normal always faces the incoming ray
+        Some(
+            HitRecord::new(t, p, self.normal, &*self.material)
+                .with_uv(alpha, beta)
+                .with_front_face(denominator < 0.0),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(vec![
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ]))
+    }
+}
+
+/// A single two-sided triangle, the front face being the one where the vertices
+/// go counter-clockwise.
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    /// Per vertex normals interpolated over the surface, the flat face normal is
+    /// used without them.
+    pub normals: Option<[Vec3; 3]>,
+    /// Per vertex texture coordinates interpolated over the surface, without them
+    /// the barycentric coordinates of the second and third vertex are used.
+    pub texcoords: Option<[[f64; 2]; 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vec3; 3], material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices,
+            normals: None,
+            texcoords: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_texcoords(mut self, texcoords: [[f64; 2]; 3]) -> Self {
+        self.texcoords = Some(texcoords);
+        self
     }
 }
 
+impl Hitable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, barycentric) = intersect_triangle(ray, self.vertices, t_min, t_max)?;
+
+        let normal = match self.normals {
+            Some(normals) => interpolate(normals, barycentric),
+            None => face_normal(self.vertices),
+        };
+        let (u, v) = interpolate_uv(self.texcoords.unwrap_or(DEFAULT_TEXCOORDS), barycentric);
+
+        Some(
+            HitRecord::new(
+                t,
+                ray.point_at_parameter(t),
+                Vec3::unit_vector(normal),
+                &*self.material,
+            )
+            .with_barycentric(barycentric)
+            .with_uv(u, v)
+            .with_front_face(is_front_face(ray, self.vertices)),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.vertices.iter().copied()))
+    }
+}
+
+/// Watertight ray-triangle intersection (Woop, Benthin and Wald), which never lets
+/// a ray slip through the shared edge of two triangles.
+///
+/// Returns the distance and the barycentric coordinates of the hit point.
+pub(crate) fn intersect_triangle(
+    ray: &Ray,
+    vertices: [Vec3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, [f64; 3])> {
+    let direction = ray.direction;
+    // degenerate rays, e.g. normalized zero vectors, miss everything
+    if !(0..3).all(|axis| direction[axis].is_finite()) {
+        return None;
+    }
+
+    // the dimension where the ray direction is largest becomes z
+    let kz = (0..3)
+        .max_by(|&a, &b| direction[a].abs().total_cmp(&direction[b].abs()))
+        .unwrap_or(2);
+    if direction[kz] == 0.0 {
+        return None;
+    }
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    // keep the winding direction of the triangle
+    if direction[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // shear the vertices so that the ray points along +z
+    let shear_x = direction[kx] / direction[kz];
+    let shear_y = direction[ky] / direction[kz];
+    let shear_z = 1.0 / direction[kz];
+
+    let relative = |vertex: Vec3| vertex - ray.origin;
+    let (a, b, c) = (
+        relative(vertices[0]),
+        relative(vertices[1]),
+        relative(vertices[2]),
+    );
+    let sheared = |vertex: Vec3| {
+        (
+            vertex[kx] - shear_x * vertex[kz],
+            vertex[ky] - shear_y * vertex[kz],
+        )
+    };
+    let ((ax, ay), (bx, by), (cx, cy)) = (sheared(a), sheared(b), sheared(c));
+
+    // scaled barycentric coordinates
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let determinant = u + v + w;
+    if determinant == 0.0 {
+        return None;
+    }
+
+    let scaled_t = shear_z * (u * a[kz] + v * b[kz] + w * c[kz]);
+    let t = scaled_t / determinant;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+
+    Some((t, [u / determinant, v / determinant, w / determinant]))
+}
+
+/// The unnormalized normal of the front face.
+pub(crate) fn face_normal(vertices: [Vec3; 3]) -> Vec3 {
+    Vec3::cross(vertices[1] - vertices[0], vertices[2] - vertices[0])
+}
+
+/// Whether the `ray` hits the counter-clockwise side of the triangle.
+pub(crate) fn is_front_face(ray: &Ray, vertices: [Vec3; 3]) -> bool {
+    Vec3::dot(face_normal(vertices), ray.direction) < 0.0
+}
+
+/// Blends the per vertex `values` with the `barycentric` weights.
+pub(crate) fn interpolate(values: [Vec3; 3], barycentric: [f64; 3]) -> Vec3 {
+    barycentric[0] * values[0] + barycentric[1] * values[1] + barycentric[2] * values[2]
+}
+
+/// The texture coordinates of triangles without any, which makes the surface
+/// coordinates the barycentric coordinates of the second and third vertex.
+pub(crate) const DEFAULT_TEXCOORDS: [[f64; 2]; 3] = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+
+/// Blends the per vertex `texcoords` with the `barycentric` weights.
+pub(crate) fn interpolate_uv(texcoords: [[f64; 2]; 3], barycentric: [f64; 3]) -> (f64, f64) {
+    texcoords
+        .iter()
+        .zip(barycentric.iter())
+        .fold((0.0, 0.0), |(u, v), (texcoord, weight)| {
+            (u + weight * texcoord[0], v + weight * texcoord[1])
+        })
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn unit_quad() -> Quad {
+        Quad::new(
+            Vec3::new(-0.5, -0.5, -1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn it_hits_quad_from_both_sides() {
+        let quad = unit_quad();
+
+        let front = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = quad.hit(&front, 0.0, 10.0).expect("Should hit the quad");
+        assert_eq!(1.0, hit_record.t);
+        assert_eq!(Vec3::new(0.0, 0.0, -1.0), hit_record.p);
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+
+        let back = Ray::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit_record = quad.hit(&back, 0.0, 10.0).expect("Should hit the quad");
+        assert_eq!(1.0, hit_record.t);
+        assert_eq!(Vec3::new(0.0, 0.0, -1.0), hit_record.normal);
+    }
+
+    #[test]
+    fn it_does_not_hit_quad() {
+        let quad = unit_quad();
+
+        let outside = Ray::new(Vec3::new(0.6, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad.hit(&outside, 0.0, 10.0).is_none());
+
+        let parallel = Ray::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(quad.hit(&parallel, 0.0, 10.0).is_none());
+
+        let too_far = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad.hit(&too_far, 0.0, 0.5).is_none());
+    }
+
+    fn unit_triangle() -> Triangle {
+        Triangle::new(
+            [
+                Vec3::new(0.0, 0.0, -1.0),
+                Vec3::new(1.0, 0.0, -1.0),
+                Vec3::new(0.0, 1.0, -1.0),
+            ],
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn it_hits_triangle_with_barycentric_coordinates() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(Vec3::new(0.25, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let hit_record = triangle.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!(1.0, hit_record.t);
+        assert_eq!(Vec3::new(0.25, 0.5, -1.0), hit_record.p);
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+        assert_eq!(Some([0.25, 0.25, 0.5]), hit_record.barycentric);
+
+        assert!(hit_record.front_face);
+
+        // triangles are two-sided, so the normal faces the ray hitting it from behind
+        let behind = Ray::new(Vec3::new(0.25, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit_record = triangle.hit(&behind, 0.0, 10.0).expect("Should hit");
+        assert_eq!(Vec3::new(0.0, 0.0, -1.0), hit_record.normal);
+        assert!(!hit_record.front_face);
+    }
+
+    #[test]
+    fn degenerate_rays_miss_triangles() {
+        let triangle = unit_triangle();
+
+        for direction in [
+            Vec3::new(f64::NAN, 0.0, -1.0),
+            Vec3::new(0.0, f64::INFINITY, -1.0),
+            Vec3::new(0.0, 0.0, 0.0),
+        ] {
+            let ray = Ray::new(Vec3::new(0.25, 0.25, 0.0), direction);
+
+            assert!(triangle.hit(&ray, 0.0, 10.0).is_none());
+        }
+    }
+
+    #[test]
+    fn diffuse_triangles_scatter_back_towards_the_ray_from_behind() {
+        let triangle = unit_triangle();
+        let behind = Ray::new(Vec3::new(0.25, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit_record = triangle.hit(&behind, 0.0, 10.0).expect("Should hit");
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            let scatter = hit_record
+                .material
+                .scatter(&behind, &hit_record, &mut rng)
+                .expect("Should scatter");
+
+            assert!(scatter.ray.direction.z() <= 0.0);
+        }
+    }
+
+    #[test]
+    fn it_interpolates_triangle_normals() {
+        let triangle = unit_triangle().with_normals([
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ]);
+        let ray = Ray::new(Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let hit_record = triangle.hit(&ray, 0.0, 10.0).expect("Should hit");
+
+        assert_eq!(
+            Vec3::unit_vector(Vec3::new(1.0, 0.0, 1.0)),
+            hit_record.normal
+        );
+    }
+
+    #[test]
+    fn it_does_not_hit_triangle() {
+        let triangle = unit_triangle();
+
+        let outside = Ray::new(Vec3::new(0.6, 0.6, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(triangle.hit(&outside, 0.0, 10.0).is_none());
+
+        let parallel = Ray::new(Vec3::new(-1.0, 0.1, -1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(triangle.hit(&parallel, 0.0, 10.0).is_none());
+
+        let too_far = Ray::new(Vec3::new(0.1, 0.1, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(triangle.hit(&too_far, 0.0, 0.5).is_none());
+    }
+
+    #[test]
+    fn rays_do_not_slip_through_shared_triangle_edges() {
+        let vertices = [
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, -1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+        ];
+        let first = [vertices[0], vertices[1], vertices[2]];
+        let second = [vertices[0], vertices[2], vertices[3]];
+
+        // rays through the shared diagonal edge
+        for step in 0..=100 {
+            let along = -1.0 + 2.0 * f64::from(step) / 100.0;
+            let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(along, along, -2.0));
+
+            assert!(
+                intersect_triangle(&ray, first, 0.0, 10.0).is_some()
+                    || intersect_triangle(&ray, second, 0.0, 10.0).is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn it_bounds_the_objects() {
+        let quad = unit_quad();
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(-0.5, -0.5, -1.0),
+                Vec3::new(0.5, 0.5, -1.0)
+            )),
+            quad.bounding_box()
+        );
+
+        let triangle = unit_triangle();
+        assert_eq!(
+            Some(Aabb::from_points(triangle.vertices.iter().copied())),
+            triangle.bounding_box()
+        );
+
+        let hollow = Sphere::new(Vec3::new(1.0, 2.0, 3.0), -0.5, quad.material.clone());
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(0.5, 1.5, 2.5),
+                Vec3::new(1.5, 2.5, 3.5)
+            )),
+            hollow.bounding_box()
+        );
+    }
+
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_the_time_of_the_ray() {
+        let sphere = MovingSphere::new(
+            Vec3::new(0.0, 0.0, -2.0),
+            Vec3::new(2.0, 0.0, -2.0),
+            0.0,
+            1.0,
+            0.5,
+            unit_quad().material,
+        );
+        let ray = Ray::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert!(sphere.hit(&ray, 0.0, 10.0).is_none());
+        let hit_record = sphere
+            .hit(&ray.with_time(0.5), 0.0, 10.0)
+            .expect("Should hit");
+        assert_eq!(1.5, hit_record.t);
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), hit_record.normal);
+        // it rests at the end of its motion
+        assert_eq!(Vec3::new(2.0, 0.0, -2.0), sphere.center(3.0));
+
+        assert_eq!(
+            Some(Aabb::new(
+                Vec3::new(-0.5, -0.5, -2.5),
+                Vec3::new(2.5, 0.5, -1.5)
+            )),
+            sphere.bounding_box()
+        );
+    }
+
+    #[test]
+    fn it_maps_the_surface_coordinates() {
+        let sphere = Sphere::new(Vec3::new(0.0, 0.0, -2.0), 0.5, unit_quad().material);
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit_record = sphere.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!((0.25, 0.5), (hit_record.u, hit_record.v));
+
+        let top = Ray::new(Vec3::new(0.0, 1.0, -2.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit_record = sphere.hit(&top, 0.0, 10.0).expect("Should hit");
+        assert_eq!(1.0, hit_record.v);
+
+        let ray = Ray::new(Vec3::new(0.25, -0.25, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let quad = unit_quad();
+        let hit_record = quad.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!((0.75, 0.25), (hit_record.u, hit_record.v));
+
+        let ray = Ray::new(Vec3::new(0.25, 0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let triangle = unit_triangle();
+        let hit_record = triangle.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!((0.25, 0.5), (hit_record.u, hit_record.v));
+
+        let textured = unit_triangle().with_texcoords([[1.0, 1.0], [0.0, 1.0], [1.0, 0.0]]);
+        let hit_record = textured.hit(&ray, 0.0, 10.0).expect("Should hit");
+        assert_eq!((0.75, 0.5), (hit_record.u, hit_record.v));
+    }
+
     #[test]
     fn it_hits_sphere_on_one_point() {
         // TODO: Implement
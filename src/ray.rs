@@ -1,13 +1,26 @@
 use crate::vec3::Vec3;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// The moment the ray is shot within the shutter interval of the camera,
+    /// which places any moving objects.
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
     }
 
     pub fn point_at_parameter(&self, t: f64) -> Vec3 {
@@ -28,5 +41,7 @@ mod test {
         assert_eq!(Vec3::new(9.0, 12.0, 15.0), ray.point_at_parameter(2.0));
         assert_eq!(&Vec3::new(1.0, 2.0, 3.0), &ray.origin);
         assert_eq!(&Vec3::new(4.0, 5.0, 6.0), &ray.direction);
+        assert_eq!(0.0, ray.time);
+        assert_eq!(0.5, ray.with_time(0.5).time);
     }
 }
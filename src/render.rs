@@ -0,0 +1,552 @@
+use std::f64;
+
+use rand::{Rng, RngCore};
+use rand_pcg::Pcg32;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::environment::Environment;
+use crate::film::Film;
+use crate::hitable::Hitable;
+use crate::image::Image;
+use crate::Camera;
+use crate::Ray;
+use crate::Vec3;
+
+/// What and how much to render.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u32,
+    /// The number of bounces after which a ray is considered absorbed.
+    pub max_depth: u32,
+    /// The width and height of the tiles which are rendered on their own.
+    pub tile_size: usize,
+    /// Renders with the same seed are identical, regardless of the number of
+    /// threads or the order of the tiles.
+    pub seed: u64,
+}
+
+impl Settings {
+    /// Splits the image into tiles in scanline order, the tiles on the right and
+    /// bottom edges might be smaller than the `tile_size`.
+    pub fn tiles(&self) -> Vec<Tile> {
+        let tile_size = self.tile_size.max(1);
+        let mut tiles = vec![];
+
+        for y in (0..self.height).step_by(tile_size) {
+            for x in (0..self.width).step_by(tile_size) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: tile_size.min(self.width - x),
+                    height: tile_size.min(self.height - y),
+                });
+            }
+        }
+
+        tiles
+    }
+
+    /// The random number generator for the `sample` of the pixel at `x` and `y`,
+    /// each of them getting an independent stream.
+    pub fn rng(&self, x: usize, y: usize, sample: u32) -> Pcg32 {
+        let pixel = split_mix((y * self.width + x) as u64);
+        let sample = u64::from(sample);
+
+        Pcg32::new(split_mix(self.seed ^ split_mix(pixel ^ sample)), sample)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            width: 200,
+            height: 100,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            tile_size: 16,
+            seed: 0,
+        }
+    }
+}
+
+/// A rectangular part of the image, `x` and `y` being its top left pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Computes the light arriving at the camera along a single ray.
+pub trait Integrator: Send + Sync {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &dyn Hitable,
+        max_depth: u32,
+        rng: &mut dyn RngCore,
+    ) -> Vec3;
+}
+
+/// Unidirectional path tracer which samples the environment directly at every
+/// diffuse bounce, combining both techniques with multiple importance sampling.
+pub struct PathTracer {
+    pub environment: Box<dyn Environment>,
+    /// The number of bounces after which paths carrying little light are randomly
+    /// terminated (Russian roulette).
+    pub russian_roulette_depth: u32,
+}
+
+impl PathTracer {
+    pub fn new(environment: Box<dyn Environment>) -> Self {
+        Self {
+            environment,
+            russian_roulette_depth: 3,
+        }
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(
+        &self,
+        ray: &Ray,
+        world: &dyn Hitable,
+        max_depth: u32,
+        rng: &mut dyn RngCore,
+    ) -> Vec3 {
+        let mut radiance = Vec3::new(0.0, 0.0, 0.0);
+        // the fraction of the light which makes it back to the camera along the path
+        let mut throughput = Vec3::new(1.0, 1.0, 1.0);
+        let mut ray = *ray;
+        // the probability density of the bounce which produced the `ray`, `None` for
+        // the camera ray and specular bounces
+        let mut scatter_pdf = None;
+
+        for depth in 0..=max_depth {
+            let hit_record = match world.hit(&ray, 0.001, f64::MAX) {
+                Some(hit_record) => hit_record,
+                None => {
+                    let environment = self.environment.radiance(ray.direction);
+                    // the environment may have been sampled directly at the previous bounce as well
+                    let weight = scatter_pdf.map_or(1.0, |pdf| {
+                        power_heuristic(pdf, self.environment.pdf(ray.direction))
+                    });
+
+                    radiance += throughput * environment * weight;
+                    break;
+                }
+            };
+
+            radiance += throughput * hit_record.material.emitted(&ray, &hit_record);
+            if depth == max_depth {
+                break;
+            }
+
+            // sample the environment directly
+            if let Some(sample) = self.environment.sample(rng) {
+                let shadow_ray = Ray::new(hit_record.p, sample.direction).with_time(ray.time);
+                let eval = hit_record
+                    .material
+                    .eval(&ray, &hit_record, sample.direction);
+
+                if let Some((value, pdf)) = eval {
+                    if sample.pdf > 0.0 && world.hit(&shadow_ray, 0.001, f64::MAX).is_none() {
+                        let weight = power_heuristic(sample.pdf, pdf);
+
+                        radiance += throughput * value * sample.radiance * weight / sample.pdf;
+                    }
+                }
+            }
+
+            let scatter = match hit_record.material.scatter(&ray, &hit_record, rng) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+            throughput *= scatter.attenuation;
+
+            if depth >= self.russian_roulette_depth {
+                let max_throughput = throughput.x().max(throughput.y()).max(throughput.z());
+                let termination = (1.0 - max_throughput).max(0.05);
+
+                if rng.gen::<f64>() < termination {
+                    break;
+                }
+                // the surviving paths make up for the terminated ones
+                throughput /= 1.0 - termination;
+            }
+
+            ray = scatter.ray;
+            scatter_pdf = scatter.pdf;
+        }
+
+        radiance
+    }
+}
+
+/// Renders images of a scene through a camera.
+pub struct Renderer<I: Integrator> {
+    pub settings: Settings,
+    pub integrator: I,
+}
+
+impl<I: Integrator> Renderer<I> {
+    pub fn new(settings: Settings, integrator: I) -> Self {
+        Self {
+            settings,
+            integrator,
+        }
+    }
+
+    /// Returns the averaged linear radiance of every pixel.
+    ///
+    /// With the `parallel` feature the tiles are rendered on all cores.
+    pub fn render(&self, world: &dyn Hitable, camera: &Camera) -> Image {
+        self.render_film(world, camera).resolve()
+    }
+
+    /// Returns the film with all the samples of every pixel, see `render`.
+    pub fn render_film(&self, world: &dyn Hitable, camera: &Camera) -> Film {
+        let tiles = self.settings.tiles();
+
+        #[cfg(feature = "parallel")]
+        let rendered: Vec<Film> = tiles
+            .par_iter()
+            .map(|tile| self.render_tile(tile, world, camera))
+            .collect();
+        #[cfg(not(feature = "parallel"))]
+        let rendered: Vec<Film> = tiles
+            .iter()
+            .map(|tile| self.render_tile(tile, world, camera))
+            .collect();
+
+        let mut film = Film::new(self.settings.width, self.settings.height);
+        for (tile, tile_film) in tiles.iter().zip(rendered) {
+            film.merge(&tile_film, tile.x, tile.y);
+        }
+
+        film
+    }
+
+    /// Returns the film of just the `tile`.
+    fn render_tile(&self, tile: &Tile, world: &dyn Hitable, camera: &Camera) -> Film {
+        let Settings {
+            width,
+            height,
+            samples_per_pixel,
+            max_depth,
+            ..
+        } = self.settings;
+        let mut film = Film::new(tile.width, tile.height);
+
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                for sample in 0..samples_per_pixel {
+                    let mut rng = self.settings.rng(x, y, sample);
+                    let jitter_x = rng.gen::<f64>();
+                    let jitter_y = rng.gen::<f64>();
+                    // the first row of the image is the top of the camera
+                    let u = (x as f64 + jitter_x) / width as f64;
+                    let v = 1.0 - (y as f64 + jitter_y) / height as f64;
+
+                    // the parts of the image the camera doesn't cover stay black
+                    let radiance = match camera.get_ray(u, v, &mut rng) {
+                        Some(ray) => self.integrator.radiance(&ray, world, max_depth, &mut rng),
+                        None => Vec3::new(0.0, 0.0, 0.0),
+                    };
+
+                    film.add_sample(
+                        (x - tile.x) as f64 + jitter_x,
+                        (y - tile.y) as f64 + jitter_y,
+                        radiance,
+                        1.0,
+                    );
+                }
+            }
+        }
+
+        film
+    }
+}
+
+/// Scrambles the bits of the `value` (SplitMix64), so that similar seeds give
+/// unrelated random number streams.
+fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    z ^ (z >> 31)
+}
+
+/// Weighs the sampling technique with probability density `pdf` against the
+/// `other_pdf` technique for multiple importance sampling.
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf = pdf.powi(2);
+    let other_pdf = other_pdf.powi(2);
+
+    if pdf + other_pdf > 0.0 {
+        pdf / (pdf + other_pdf)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::environment::Constant;
+    use crate::hitable::HitableList;
+    use crate::material::DiffuseLight;
+    use crate::material::Lambertian;
+    use crate::object::Quad;
+    use crate::object::Sphere;
+
+    #[test]
+    fn it_renders_the_environment_of_an_empty_scene() {
+        let settings = Settings {
+            width: 4,
+            height: 3,
+            samples_per_pixel: 2,
+            max_depth: 5,
+            tile_size: 3,
+            seed: 0,
+        };
+        let renderer = Renderer::new(
+            settings,
+            PathTracer::new(Box::new(Constant::new(Vec3::new(0.25, 0.5, 1.0)))),
+        );
+
+        let image = renderer.render(&HitableList::new(), &Camera::default());
+
+        assert_eq!(4, image.width());
+        assert_eq!(3, image.height());
+        assert!(image
+            .pixels()
+            .iter()
+            .all(|pixel| *pixel == Vec3::new(0.25, 0.5, 1.0)));
+    }
+
+    #[test]
+    fn it_splits_the_image_into_tiles() {
+        let settings = Settings {
+            width: 5,
+            height: 3,
+            tile_size: 2,
+            ..Settings::default()
+        };
+
+        let tile = |x, y, width, height| Tile {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(
+            vec![
+                tile(0, 0, 2, 2),
+                tile(2, 0, 2, 2),
+                tile(4, 0, 1, 2),
+                tile(0, 2, 2, 1),
+                tile(2, 2, 2, 1),
+                tile(4, 2, 1, 1),
+            ],
+            settings.tiles()
+        );
+    }
+
+    #[test]
+    fn it_assembles_the_tiles_into_the_image() {
+        let settings = Settings {
+            width: 5,
+            height: 4,
+            samples_per_pixel: 1,
+            max_depth: 5,
+            tile_size: 2,
+            seed: 0,
+        };
+        let renderer = Renderer::new(
+            settings,
+            PathTracer::new(Box::new(Constant::new(Vec3::new(0.0, 0.0, 0.0)))),
+        );
+        // a light filling the left half of the view
+        let objects = vec![Quad::new(
+            Vec3::new(-10.0, -10.0, -1.0),
+            Vec3::new(10.0, 0.0, 0.0),
+            Vec3::new(0.0, 20.0, 0.0),
+            Arc::new(DiffuseLight::new(Vec3::new(1.0, 1.0, 1.0))),
+        )];
+
+        let image = renderer.render(&HitableList::from(objects), &Camera::default());
+
+        for y in 0..4 {
+            assert_eq!(Vec3::new(1.0, 1.0, 1.0), image.get(0, y));
+            assert_eq!(Vec3::new(1.0, 1.0, 1.0), image.get(1, y));
+            assert_eq!(Vec3::new(0.0, 0.0, 0.0), image.get(3, y));
+            assert_eq!(Vec3::new(0.0, 0.0, 0.0), image.get(4, y));
+        }
+    }
+
+    #[test]
+    fn it_accumulates_every_sample_on_the_film() {
+        let settings = Settings {
+            width: 5,
+            height: 3,
+            samples_per_pixel: 3,
+            max_depth: 5,
+            tile_size: 2,
+            seed: 0,
+        };
+        let renderer = Renderer::new(
+            settings,
+            PathTracer::new(Box::new(Constant::new(Vec3::new(0.5, 0.5, 0.5)))),
+        );
+
+        let film = renderer.render_film(&HitableList::new(), &Camera::default());
+
+        assert_eq!((5, 3), (film.width(), film.height()));
+        for pixel in film.pixels() {
+            assert_eq!(3, pixel.samples);
+            assert_eq!(3.0, pixel.weight);
+            assert_eq!(Vec3::new(1.5, 1.5, 1.5), pixel.radiance);
+        }
+    }
+
+    #[test]
+    fn renders_with_the_same_seed_are_identical() {
+        let render = |tile_size, seed| {
+            let settings = Settings {
+                width: 12,
+                height: 6,
+                samples_per_pixel: 4,
+                max_depth: 10,
+                tile_size,
+                seed,
+            };
+            let renderer = Renderer::new(
+                settings,
+                PathTracer::new(Box::new(Constant::new(Vec3::new(1.0, 1.0, 1.0)))),
+            );
+            let objects = vec![
+                Sphere::new(
+                    Vec3::new(0.0, 0.0, -1.0),
+                    0.5,
+                    Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+                ),
+                Sphere::new(
+                    Vec3::new(0.0, -100.5, -1.0),
+                    100.0,
+                    Arc::new(Lambertian::new(Vec3::new(0.8, 0.8, 0.0))),
+                ),
+            ];
+
+            renderer.render(&HitableList::from(objects), &Camera::default())
+        };
+
+        assert_eq!(render(4, 7), render(4, 7));
+        // the tiles are rendered in a different order and on different threads
+        assert_eq!(render(4, 7), render(5, 7));
+        assert_ne!(render(4, 7), render(4, 8));
+    }
+
+    #[test]
+    fn every_pixel_sample_gets_its_own_rng() {
+        let settings = Settings::default();
+
+        assert_eq!(
+            settings.rng(1, 2, 3).gen::<u64>(),
+            settings.rng(1, 2, 3).gen::<u64>()
+        );
+        assert_ne!(
+            settings.rng(1, 2, 3).gen::<u64>(),
+            settings.rng(1, 2, 4).gen::<u64>()
+        );
+        assert_ne!(
+            settings.rng(1, 2, 3).gen::<u64>(),
+            settings.rng(2, 1, 3).gen::<u64>()
+        );
+    }
+
+    #[test]
+    fn path_tracer_sees_the_light_sources() {
+        let path_tracer = PathTracer::new(Box::new(Constant::new(Vec3::new(0.0, 0.0, 0.0))));
+        let objects = vec![Sphere::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(DiffuseLight::new(Vec3::new(4.0, 2.0, 1.0))),
+        )];
+        let world = HitableList::from(objects);
+        let mut rng = thread_rng();
+
+        let towards = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(
+            Vec3::new(4.0, 2.0, 1.0),
+            path_tracer.radiance(&towards, &world, 5, &mut rng)
+        );
+
+        let away = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            path_tracer.radiance(&away, &world, 5, &mut rng)
+        );
+    }
+
+    #[test]
+    fn path_tracer_stops_at_the_max_depth() {
+        let path_tracer = PathTracer::new(Box::new(Constant::new(Vec3::new(1.0, 1.0, 1.0))));
+        let objects = vec![Sphere::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            0.5,
+            Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5))),
+        )];
+        let world = HitableList::from(objects);
+        let mut rng = thread_rng();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            path_tracer.radiance(&ray, &world, 0, &mut rng)
+        );
+        // a single bounce off a convex object always escapes to the environment
+        assert_eq!(
+            Vec3::new(0.5, 0.5, 0.5),
+            path_tracer.radiance(&ray, &world, 1, &mut rng)
+        );
+    }
+
+    #[test]
+    fn path_tracer_terminates_paths_in_enclosed_scenes() {
+        let path_tracer = PathTracer::new(Box::new(Constant::new(Vec3::new(1.0, 1.0, 1.0))));
+        // white surfaces never absorb anything, so only Russian roulette ends the paths
+        let white = Arc::new(Lambertian::new(Vec3::new(1.0, 1.0, 1.0)));
+        let objects = vec![
+            // the camera is inside of the hollow outer sphere so no light can ever reach it
+            Sphere::new(Vec3::new(0.0, 0.0, 0.0), -10.0, white.clone()),
+            Sphere::new(Vec3::new(0.0, 0.0, -2.0), 1.0, white),
+        ];
+        let world = HitableList::from(objects);
+        let mut rng = thread_rng();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.1, 0.2, -1.0));
+
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            path_tracer.radiance(&ray, &world, u32::MAX, &mut rng)
+        );
+    }
+
+    #[test]
+    fn power_heuristic_favours_the_technique_with_higher_pdf() {
+        assert_eq!(1.0, power_heuristic(1.0, 0.0));
+        assert_eq!(0.0, power_heuristic(0.0, 1.0));
+        assert_eq!(0.5, power_heuristic(2.0, 2.0));
+        assert_eq!(0.8, power_heuristic(2.0, 1.0));
+        assert_eq!(0.0, power_heuristic(0.0, 0.0));
+    }
+}
@@ -0,0 +1,50 @@
+//! Helpers shared by the tests of several modules.
+
+use std::path::{Path, PathBuf};
+
+use crate::Vec3;
+
+/// Asserts that the vectors are equal up to floating point rounding errors.
+#[track_caller]
+pub(crate) fn assert_vec3_eq(expected: Vec3, actual: Vec3) {
+    assert_vec3_near(expected, actual, 1e-10);
+}
+
+/// Asserts that the vectors are no further than `tolerance` apart, e.g. for
+/// values which went through single precision.
+#[track_caller]
+pub(crate) fn assert_vec3_near(expected: Vec3, actual: Vec3, tolerance: f64) {
+    assert!(
+        (expected - actual).length() < tolerance,
+        "{:?} != {:?}",
+        expected,
+        actual
+    );
+}
+
+/// A directory for the files of a single test, unique to the test and the running
+/// process so concurrent test runs don't race on the same files. The directory is
+/// removed along with its contents when dropped.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new(test_name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("ray_trace_{}_{}", std::process::id(), test_name));
+        std::fs::create_dir_all(&path).expect("Should create the directory");
+
+        Self { path }
+    }
+
+    pub(crate) fn join<P: AsRef<Path>>(&self, file_name: P) -> PathBuf {
+        self.path.join(file_name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,340 @@
+use std::io::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::image::{Image, WrapMode};
+use crate::noise::{Perlin, Worley};
+use crate::Vec3;
+
+/// A color which varies over the surface of an object.
+pub trait Texture: Send + Sync {
+    /// The color at the surface coordinates `u` and `v` of the point `p`.
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3;
+}
+
+/// The same color everywhere.
+pub struct SolidColor {
+    pub color: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(color: Vec3) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+/// A 3D checkerboard of cubes with sides of length `scale`, alternating between
+/// the `even` and `odd` textures.
+///
+/// It is defined in space rather than over the surface, so it doesn't get
+/// stretched by the surface coordinates.
+pub struct Checker {
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+    pub scale: f64,
+}
+
+impl Checker {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f64) -> Self {
+        Self { even, odd, scale }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Vec3) -> Vec3 {
+        let cell = (0..3)
+            .map(|axis| (p[axis] / self.scale).floor() as i64)
+            .sum::<i64>();
+
+        if cell % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// A bilinearly filtered image stretched over the surface, with `v` going from the
+/// bottom to the top of the image.
+///
+/// Surface coordinates outside of `[0, 1]` are clamped to the edge of the image
+/// unless the texture is set to repeat.
+pub struct ImageTexture {
+    pub image: Arc<Image>,
+    pub wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(image: Arc<Image>) -> Self {
+        Self {
+            image,
+            wrap: WrapMode::Clamp,
+        }
+    }
+
+    /// Loads the image from a PPM, PNG or Radiance HDR file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(Arc::new(Image::open(path)?)))
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vec3) -> Vec3 {
+        self.image.sample(u, 1.0 - v, self.wrap)
+    }
+}
+
+/// Grey turbulent noise, e.g. for clouds or smoke.
+pub struct Turbulence {
+    pub noise: Perlin,
+    /// The frequency of the noise, the bigger the finer the pattern.
+    pub scale: f64,
+    pub depth: usize,
+}
+
+impl Turbulence {
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            depth: 7,
+        }
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+impl Texture for Turbulence {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let value = self.noise.turbulence(self.scale * p, self.depth).min(1.0);
+
+        Vec3::new(value, value, value)
+    }
+}
+
+/// Veins running across the z axis, distorted by turbulence.
+pub struct Marble {
+    pub noise: Perlin,
+    /// The frequency of the veins, the bigger the more of them.
+    pub scale: f64,
+    pub light: Vec3,
+    pub dark: Vec3,
+}
+
+impl Marble {
+    /// White marble with dark grey veins.
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            light: Vec3::new(0.9, 0.9, 0.9),
+            dark: Vec3::new(0.1, 0.1, 0.1),
+        }
+    }
+
+    pub fn with_colors(mut self, light: Vec3, dark: Vec3) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let p = self.scale * p;
+        let phase = p.z() + 10.0 * self.noise.turbulence(p, 7);
+
+        blend(self.dark, self.light, 0.5 * (1.0 + phase.sin()))
+    }
+}
+
+/// Growth rings around the y axis, wobbled by noise.
+pub struct Wood {
+    pub noise: Perlin,
+    /// The number of rings per unit.
+    pub scale: f64,
+    pub light: Vec3,
+    pub dark: Vec3,
+}
+
+impl Wood {
+    /// Light brown wood with dark brown rings.
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            light: Vec3::new(0.75, 0.55, 0.3),
+            dark: Vec3::new(0.4, 0.22, 0.08),
+        }
+    }
+
+    pub fn with_colors(mut self, light: Vec3, dark: Vec3) -> Self {
+        self.light = light;
+        self.dark = dark;
+        self
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let p = self.scale * p;
+        let radius = (p.x().powi(2) + p.z().powi(2)).sqrt();
+        let ring = radius + 0.5 * self.noise.fbm(p, 4, 2.0, 0.5);
+
+        blend(self.light, self.dark, ring - ring.floor())
+    }
+}
+
+/// Cells around randomly scattered points, from the `center` color at the points
+/// to the `edge` color where the cells meet.
+pub struct Cellular {
+    pub noise: Worley,
+    /// The number of cells per unit.
+    pub scale: f64,
+    pub center: Vec3,
+    pub edge: Vec3,
+}
+
+impl Cellular {
+    /// Black cells with white edges.
+    pub fn new(noise: Worley, scale: f64) -> Self {
+        Self {
+            noise,
+            scale,
+            center: Vec3::new(0.0, 0.0, 0.0),
+            edge: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn with_colors(mut self, center: Vec3, edge: Vec3) -> Self {
+        self.center = center;
+        self.edge = edge;
+        self
+    }
+}
+
+impl Texture for Cellular {
+    fn value(&self, _u: f64, _v: f64, p: Vec3) -> Vec3 {
+        let distance = self.noise.distance(self.scale * p).min(1.0);
+
+        blend(self.center, self.edge, distance)
+    }
+}
+
+/// Linear interpolation from `from` at `0.0` to `to` at `1.0`.
+fn blend(from: Vec3, to: Vec3, t: f64) -> Vec3 {
+    (1.0 - t) * from + t * to
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_pcg::Pcg32;
+
+    use super::*;
+
+    fn solid(value: f64) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::new(Vec3::new(value, value, value)))
+    }
+
+    #[test]
+    fn checker_alternates_between_the_cells() {
+        let checker = Checker::new(solid(1.0), solid(0.0), 0.5);
+
+        assert_eq!(
+            Vec3::new(1.0, 1.0, 1.0),
+            checker.value(0.0, 0.0, Vec3::new(0.25, 0.25, 0.25))
+        );
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            checker.value(0.0, 0.0, Vec3::new(0.75, 0.25, 0.25))
+        );
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.0),
+            checker.value(0.0, 0.0, Vec3::new(-0.25, 0.25, 0.25))
+        );
+        assert_eq!(
+            Vec3::new(1.0, 1.0, 1.0),
+            checker.value(0.0, 0.0, Vec3::new(-0.25, -0.25, 0.25))
+        );
+    }
+
+    #[test]
+    fn image_texture_looks_up_the_texels() {
+        let mut image = Image::new(2, 2);
+        image.set(0, 0, Vec3::new(1.0, 0.0, 0.0));
+        image.set(1, 1, Vec3::new(0.0, 0.0, 1.0));
+        let texture = ImageTexture::new(Arc::new(image));
+        let p = Vec3::new(0.0, 0.0, 0.0);
+
+        // the top left corner
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), texture.value(0.25, 0.75, p));
+        // the bottom right corner
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), texture.value(0.75, 0.25, p));
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), texture.value(1.0, 0.0, p));
+        assert_eq!(Vec3::new(0.0, 0.0, 1.0), texture.value(3.0, -1.0, p));
+        // the image is filtered between the texel centers
+        assert_eq!(Vec3::new(0.25, 0.0, 0.25), texture.value(0.5, 0.5, p));
+
+        let texture = texture.with_wrap(WrapMode::Repeat);
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), texture.value(2.25, 1.75, p));
+    }
+
+    #[test]
+    fn procedural_textures_stay_between_their_colors() {
+        let light = Vec3::new(1.0, 0.8, 0.6);
+        let dark = Vec3::new(0.2, 0.1, 0.0);
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(Turbulence::new(Perlin::new(&mut Pcg32::new(1, 2)), 4.0)),
+            Box::new(Marble::new(Perlin::new(&mut Pcg32::new(1, 2)), 4.0).with_colors(light, dark)),
+            Box::new(Wood::new(Perlin::new(&mut Pcg32::new(1, 2)), 4.0).with_colors(light, dark)),
+            Box::new(
+                Cellular::new(Worley::new(&mut Pcg32::new(1, 2)), 4.0).with_colors(dark, light),
+            ),
+        ];
+
+        for texture in &textures {
+            let mut values = (0..200).map(|index| {
+                let index = f64::from(index);
+                texture.value(
+                    0.0,
+                    0.0,
+                    Vec3::new(0.11 * index, -0.07 * index, 0.05 * index),
+                )
+            });
+            let first = values.next().expect("Should have values");
+
+            let mut varies = false;
+            for value in values {
+                for channel in 0..3 {
+                    assert!(value[channel] >= dark[channel].min(0.0) - 1e-12);
+                    assert!(value[channel] <= light[channel].max(1.0) + 1e-12);
+                }
+                varies |= (value - first).length() > 0.1;
+            }
+            assert!(varies);
+        }
+    }
+
+    #[test]
+    fn wood_rings_repeat_with_the_distance_from_the_axis() {
+        let wood = Wood::new(Perlin::new(&mut Pcg32::new(1, 2)), 1.0);
+
+        // the noise is zero on the integer lattice, so the rings there are unwobbled
+        assert_eq!(wood.light, wood.value(0.0, 0.0, Vec3::new(1.0, 0.0, 0.0)));
+        assert_eq!(wood.light, wood.value(0.0, 0.0, Vec3::new(0.0, 5.0, 3.0)));
+    }
+}
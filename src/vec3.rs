@@ -76,9 +76,47 @@ impl Vec3 {
         Self::new(
             left.e[1] * rhs.e[2] - left.e[2] * rhs.e[1],
             -(left.e[0] * rhs.e[2] - left.e[2] * rhs.e[0]),
-            left.e[0] * rhs.e[1] - left.e[1] * rhs.e[2],
+            left.e[0] * rhs.e[1] - left.e[1] * rhs.e[0],
         )
     }
+
+    /// The smaller of the two values of every component.
+    pub fn min(left: Self, rhs: Self) -> Self {
+        Self::new(
+            left.e[0].min(rhs.e[0]),
+            left.e[1].min(rhs.e[1]),
+            left.e[2].min(rhs.e[2]),
+        )
+    }
+
+    /// The larger of the two values of every component.
+    pub fn max(left: Self, rhs: Self) -> Self {
+        Self::new(
+            left.e[0].max(rhs.e[0]),
+            left.e[1].max(rhs.e[1]),
+            left.e[2].max(rhs.e[2]),
+        )
+    }
+
+    /// Mirrors `vec3` around the `normal`.
+    pub fn reflect(vec3: Self, normal: Self) -> Self {
+        vec3 - 2.0 * Self::dot(vec3, normal) * normal
+    }
+
+    /// Bends `vec3` through a surface with the given `normal` using Snell's law,
+    /// where `ni_over_nt` is the ratio of the refractive indices.
+    /// Returns `None` on total internal reflection.
+    pub fn refract(vec3: Self, normal: Self, ni_over_nt: f64) -> Option<Self> {
+        let unit_vector = Self::unit_vector(vec3);
+        let dt = Self::dot(unit_vector, normal);
+        let discriminant = 1.0 - ni_over_nt.powi(2) * (1.0 - dt.powi(2));
+
+        if discriminant > 0.0 {
+            Some(ni_over_nt * (unit_vector - normal * dt) - normal * discriminant.sqrt())
+        } else {
+            None
+        }
+    }
 }
 
 impl Neg for Vec3 {
@@ -527,11 +565,44 @@ mod tests {
         let vec3_left = Vec3::new(2.0, 4.0, 6.0);
         let vec3_rhs = Vec3::new(3.0, 5.0, 7.0);
 
-        assert_eq!(
-            Vec3::new(-2.0, 4.0, -18.0),
-            Vec3::cross(vec3_left, vec3_rhs)
-        );
+        assert_eq!(Vec3::new(-2.0, 4.0, -2.0), Vec3::cross(vec3_left, vec3_rhs));
         assert_eq!(Vec3::new(2.0, 4.0, 6.0), vec3_left);
         assert_eq!(Vec3::new(3.0, 5.0, 7.0), vec3_rhs);
     }
+
+    #[test]
+    fn it_takes_the_min_and_max_of_every_component() {
+        let vec3_left = Vec3::new(2.0, -4.0, 6.0);
+        let vec3_rhs = Vec3::new(3.0, 5.0, -7.0);
+
+        assert_eq!(Vec3::new(2.0, -4.0, -7.0), Vec3::min(vec3_left, vec3_rhs));
+        assert_eq!(Vec3::new(3.0, 5.0, 6.0), Vec3::max(vec3_left, vec3_rhs));
+    }
+
+    #[test]
+    fn it_reflects_a_vec3_around_a_normal() {
+        let vec3 = Vec3::new(1.0, -1.0, 0.0);
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+
+        assert_eq!(Vec3::new(1.0, 1.0, 0.0), Vec3::reflect(vec3, normal));
+    }
+
+    #[test]
+    fn it_refracts_a_vec3_through_a_surface() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+
+        // head on rays pass straight through
+        assert_eq!(
+            Some(Vec3::new(0.0, -1.0, 0.0)),
+            Vec3::refract(Vec3::new(0.0, -2.0, 0.0), normal, 1.5)
+        );
+
+        // grazing rays leaving a denser medium are reflected instead
+        let grazing = Vec3::new(1.0, -0.1, 0.0);
+        assert_eq!(None, Vec3::refract(grazing, normal, 1.5));
+
+        let refracted = Vec3::refract(grazing, normal, 1.0 / 1.5).expect("Should refract");
+        assert!(refracted.y() < 0.0);
+        assert!((refracted.length() - 1.0).abs() < 1e-10);
+    }
 }